@@ -0,0 +1,25 @@
+// Copyright © 2016, Peter Atashian
+// Licensed under the MIT License <LICENSE.md>
+extern crate wio;
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use wio::{
+    apc::queue_current,
+    sleep::{sleep_alertable, WakeReason},
+};
+static RAN: AtomicBool = AtomicBool::new(false);
+fn main() {
+    queue_current(|| {
+        println!("Hello from an APC!");
+        RAN.store(true, Ordering::SeqCst);
+    })
+    .unwrap();
+    // The APC only runs once the thread enters an alertable wait
+    match sleep_alertable(Duration::from_secs(1)) {
+        WakeReason::CallbacksFired { elapsed, .. } => println!("Woken after {:?}", elapsed),
+        WakeReason::TimedOut { .. } => panic!("the APC did not interrupt the sleep"),
+    }
+    assert!(RAN.load(Ordering::SeqCst));
+}
@@ -3,37 +3,50 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // All files in the project carrying such notice may not be copied, modified, or distributed
 // except according to those terms.
-use {Result, k32, last_error, w};
-use std::os::windows::io::{AsRawHandle};
-use thread::{Thread};
+//! Asynchronous procedure calls.
+//! A queued function only runs once the target thread enters an alertable wait, such as
+//! `SleepEx` or `WaitForSingleObjectEx` with `bAlertable` set.
+use error::{Error, Result};
+use std::os::windows::io::AsRawHandle;
+use thread::Thread;
+use winapi::{
+    shared::basetsd::ULONG_PTR,
+    um::{
+        processthreadsapi::{GetCurrentThread, QueueUserAPC},
+        winnt::HANDLE,
+    },
+};
 
-pub fn queue<T>(func: T, thread: &Thread) -> Result<()> where T: FnOnce() + 'static {
-    unsafe extern "system" fn helper<T: FnOnce() + 'static>(thing: w::ULONG_PTR) {
-        let func = Box::from_raw(thing as *mut T);
-        func()
-    }
-    let thing = Box::into_raw(Box::new(func)) as w::ULONG_PTR;
-    match unsafe { k32::QueueUserAPC(Some(helper::<T>), thread.as_raw_handle(), thing) } {
-        0 => {
-            // If it fails we still need to deallocate the function
-            unsafe { Box::from_raw(thing as *mut T) };
-            last_error()
-        },
-        _ => Ok(()),
-    }
+/// Queues a function to run on another thread.
+pub fn queue<T>(func: T, thread: &Thread) -> Result<()>
+where
+    T: FnOnce() + Send + 'static,
+{
+    unsafe { queue_raw(func, thread.as_raw_handle()) }
+}
+/// Queues a function to run on the current thread.
+pub fn queue_current<T>(func: T) -> Result<()>
+where
+    T: FnOnce() + 'static,
+{
+    unsafe { queue_raw(func, GetCurrentThread()) }
 }
-pub fn queue_current<T>(func: T) -> Result<()> where T: FnOnce() + 'static {
-    unsafe extern "system" fn helper<T: FnOnce() + 'static>(thing: w::ULONG_PTR) {
+// The function must be `Send` unless the thread is the current one
+unsafe fn queue_raw<T>(func: T, thread: HANDLE) -> Result<()>
+where
+    T: FnOnce() + 'static,
+{
+    unsafe extern "system" fn helper<T: FnOnce() + 'static>(thing: ULONG_PTR) {
         let func = Box::from_raw(thing as *mut T);
         func()
     }
-    let thing = Box::into_raw(Box::new(func)) as w::ULONG_PTR;
-    match unsafe { k32::QueueUserAPC(Some(helper::<T>), k32::GetCurrentThread(), thing) } {
+    let thing = Box::into_raw(Box::new(func)) as ULONG_PTR;
+    match QueueUserAPC(Some(helper::<T>), thread, thing) {
         0 => {
             // If it fails we still need to deallocate the function
-            unsafe { Box::from_raw(thing as *mut T) };
-            last_error()
-        },
+            drop(Box::from_raw(thing as *mut T));
+            Error::last_result()
+        }
         _ => Ok(()),
     }
 }
@@ -6,23 +6,46 @@
 use crate::wide::{FromWide, ToWide};
 use std::{
     alloc::{handle_alloc_error, Layout},
+    char::{decode_utf16, REPLACEMENT_CHARACTER},
+    cmp::Ordering,
     convert::TryInto,
     ffi::{OsStr, OsString},
+    fmt::{Debug, Display, Error as FmtError, Formatter, Write},
+    hash::{Hash, Hasher},
+    mem::forget,
+    ops::Deref,
     path::PathBuf,
+    ptr::null,
     slice::from_raw_parts,
 };
 use winapi::{
     shared::wtypes::BSTR,
     um::oleauto::{
-        SysAllocStringByteLen, SysAllocStringLen, SysFreeString, SysStringByteLen, SysStringLen,
+        SysAllocStringByteLen, SysAllocStringLen, SysFreeString, SysReAllocStringLen,
+        SysStringByteLen, SysStringLen,
     },
 };
-#[derive(Debug)]
+#[repr(transparent)]
 pub struct BStr(BSTR);
 impl BStr {
     pub unsafe fn from_raw(s: BSTR) -> BStr {
         BStr(s)
     }
+    /// Borrows a BSTR owned by something else, such as a `VARIANT`.
+    pub(crate) unsafe fn from_raw_ref(s: &BSTR) -> &BStr {
+        &*(s as *const BSTR as *const BStr)
+    }
+    /// Allocates a valid zero length BSTR, which is distinct from a null BSTR.
+    /// Like any other BSTR it is freed on drop.
+    pub fn empty() -> BStr {
+        unsafe {
+            let ptr = SysAllocStringLen(null(), 0);
+            if ptr.is_null() {
+                handle_alloc_error(Layout::array::<u16>(0).unwrap())
+            }
+            BStr(ptr)
+        }
+    }
     pub fn from_wide(s: &[u16]) -> BStr {
         unsafe {
             let ptr = SysAllocStringLen(s.as_ptr(), s.len().try_into().unwrap());
@@ -41,18 +64,51 @@ impl BStr {
             BStr(ptr)
         }
     }
+    /// Concatenates two strings into a newly allocated one.
+    pub fn concat(a: &BStr, b: &BStr) -> BStr {
+        let mut s = a.clone();
+        s.append(b.as_wide());
+        s
+    }
+    /// Grows the string in place using `SysReAllocStringLen` and copies `other` onto the end.
+    pub fn append(&mut self, other: &[u16]) {
+        let len = self.len();
+        let new_len = len.checked_add(other.len()).unwrap();
+        unsafe {
+            // A null source keeps the existing contents of the reallocated string
+            if SysReAllocStringLen(&mut self.0, null(), new_len.try_into().unwrap()) == 0 {
+                handle_alloc_error(Layout::array::<u16>(new_len).unwrap())
+            }
+            other
+                .as_ptr()
+                .copy_to_nonoverlapping(self.0.add(len), other.len());
+        }
+    }
     pub fn len(&self) -> usize {
         unsafe { SysStringLen(self.0) as usize }
     }
+    /// Whether the length is zero, which is also the case for a null BSTR.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
     pub fn byte_len(&self) -> usize {
         unsafe { SysStringByteLen(self.0) as usize }
     }
     pub fn is_null(&self) -> bool {
         self.0.is_null()
     }
+    /// Obtains the raw pointer without transferring ownership.
     pub fn as_ptr(&self) -> BSTR {
         self.0
     }
+    /// Extracts the raw pointer, transferring ownership to the caller.
+    /// You are now responsible for releasing it with `SysFreeString`, such as when returning it
+    /// through a COM out-parameter.
+    pub fn into_raw(self) -> BSTR {
+        let p = self.0;
+        forget(self);
+        p
+    }
     pub fn as_wide(&self) -> &[u16] {
         if self.0.is_null() {
             &[]
@@ -74,11 +130,14 @@ impl BStr {
             unsafe { from_raw_parts(self.0.cast(), self.byte_len()) }
         }
     }
+    /// Includes the terminator, which is a full two byte wide nul.
+    /// This is only meaningful when `byte_len` is even, otherwise the terminator is misaligned
+    /// with respect to the wide characters.
     pub fn as_bytes_null(&self) -> &[u8] {
         if self.0.is_null() {
-            &[0]
+            &[0, 0]
         } else {
-            unsafe { from_raw_parts(self.0.cast(), self.byte_len() + 1) }
+            unsafe { from_raw_parts(self.0.cast(), self.byte_len() + 2) }
         }
     }
     pub fn to_string(&self) -> Option<String> {
@@ -96,6 +155,65 @@ impl Clone for BStr {
         BStr::from_wide(self.as_wide())
     }
 }
+/// Derefs to the string without the nul terminator, the same as `as_wide`.
+impl Deref for BStr {
+    type Target = [u16];
+    fn deref(&self) -> &[u16] {
+        self.as_wide()
+    }
+}
+impl AsRef<[u16]> for BStr {
+    fn as_ref(&self) -> &[u16] {
+        self.as_wide()
+    }
+}
+// Comparisons use the length prefix, so embedded nuls are significant.
+impl PartialEq for BStr {
+    fn eq(&self, other: &BStr) -> bool {
+        self.as_wide() == other.as_wide()
+    }
+}
+impl Eq for BStr {}
+impl PartialOrd for BStr {
+    fn partial_cmp(&self, other: &BStr) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for BStr {
+    fn cmp(&self, other: &BStr) -> Ordering {
+        self.as_wide().cmp(other.as_wide())
+    }
+}
+impl Hash for BStr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_wide().hash(state)
+    }
+}
+impl Debug for BStr {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        f.write_char('"')?;
+        for c in decode_utf16(self.as_wide().iter().cloned()) {
+            match c {
+                Ok(c) => {
+                    for c in c.escape_debug() {
+                        f.write_char(c)?;
+                    }
+                }
+                Err(e) => write!(f, "\\u{{{:x}}}", e.unpaired_surrogate())?,
+            }
+        }
+        f.write_char('"')
+    }
+}
+/// Unpaired surrogates are replaced with U+FFFD.
+impl Display for BStr {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        for c in decode_utf16(self.as_wide().iter().cloned()) {
+            f.write_char(c.unwrap_or(REPLACEMENT_CHARACTER))?;
+        }
+        Ok(())
+    }
+}
 impl Drop for BStr {
     fn drop(&mut self) {
         unsafe { SysFreeString(self.0) };
@@ -121,3 +239,17 @@ impl From<&BStr> for PathBuf {
 }
 unsafe impl Send for BStr {}
 unsafe impl Sync for BStr {}
+
+#[cfg(test)]
+mod tests {
+    use super::BStr;
+
+    #[test]
+    fn bytes_null_terminated() {
+        let s = BStr::from_bytes(b"abcd");
+        let bytes = s.as_bytes_null();
+        assert_eq!(bytes.len(), 6);
+        assert_eq!(&bytes[..4], b"abcd");
+        assert_eq!(&bytes[4..], &[0, 0]);
+    }
+}
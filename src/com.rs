@@ -3,10 +3,40 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // All files in the project carrying such notice may not be copied, modified, or distributed
 // except according to those terms.
+use bstr::BStr;
+use error::Error;
+use std::convert::TryFrom;
+use std::ffi::OsString;
 use std::fmt::{Debug, Error as FmtError, Formatter};
-use std::mem::forget;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::mem::{forget, size_of, zeroed, MaybeUninit};
 use std::ops::Deref;
-use std::ptr::{null_mut, NonNull};
+use std::ptr::{null_mut, read, NonNull};
+use std::slice::from_raw_parts;
+use wide::FromWide;
+use winapi::shared::guiddef::GUID;
+use winapi::shared::minwindef::{DWORD, ULONG};
+use winapi::shared::ntdef::LONG;
+use winapi::shared::winerror::{
+    DISP_E_BADVARTYPE, E_INVALIDARG, E_NOINTERFACE, E_OUTOFMEMORY, E_POINTER, HRESULT, S_FALSE,
+};
+use winapi::shared::wtypes::{
+    VARENUM, VARIANT_FALSE, VARIANT_TRUE, VARTYPE, VT_BOOL, VT_BSTR, VT_DISPATCH, VT_I1, VT_I2,
+    VT_I4, VT_I8, VT_R4, VT_R8, VT_UI1, VT_UI2, VT_UI4, VT_UI8, VT_UNKNOWN,
+};
+use winapi::shared::wtypesbase::LPOLESTR;
+use winapi::um::combaseapi::{
+    CoCreateInstance, CoGetClassObject, CoInitializeEx, CoTaskMemFree, CoUninitialize,
+};
+use winapi::um::oaidl::{SAFEARRAY, VARIANT};
+use winapi::um::objbase::{COINIT_APARTMENTTHREADED, COINIT_MULTITHREADED};
+use winapi::um::objidlbase::{IEnumString, IEnumUnknown};
+use winapi::um::oleauto::{
+    SafeArrayAccessData, SafeArrayCreateVector, SafeArrayDestroy, SafeArrayGetLBound,
+    SafeArrayGetUBound, SafeArrayUnaccessData, VariantChangeType, VariantClear, VariantCopy,
+    VariantInit,
+};
 use winapi::um::unknwnbase::IUnknown;
 use winapi::Interface;
 
@@ -33,6 +63,27 @@ impl<T> ComPtr<T> {
     {
         ComPtr(NonNull::new(ptr).expect("ptr should not be null"))
     }
+    /// Creates a `ComPtr` to retain a borrowed raw pointer.
+    /// Unlike `from_raw` it does __not__ take ownership, instead it calls `AddRef`.
+    /// `T` __must__ be a COM interface that inherits from `IUnknown`.
+    /// The raw pointer must not be null or this function will panic.
+    pub unsafe fn from_raw_add_ref(ptr: *mut T) -> ComPtr<T>
+    where
+        T: Interface,
+    {
+        ComPtr::from_raw_add_ref_opt(ptr).expect("ptr should not be null")
+    }
+    /// Creates a `ComPtr` to retain a borrowed raw pointer.
+    /// Unlike `new` it does __not__ take ownership, instead it calls `AddRef`.
+    /// `T` __must__ be a COM interface that inherits from `IUnknown`.
+    pub unsafe fn from_raw_add_ref_opt(ptr: *mut T) -> Option<ComPtr<T>>
+    where
+        T: Interface,
+    {
+        let com = ComPtr::new(ptr)?;
+        com.as_unknown().AddRef();
+        Some(com)
+    }
     /// Simplifies the common pattern of calling a function to initialize a ComPtr.
     /// May leak the COM pointer if the function panics after initializing the pointer.
     /// The pointer provided to the function starts as a null pointer.
@@ -72,22 +123,83 @@ impl<T> ComPtr<T> {
         forget(self);
         p
     }
-    /// For internal use only.
-    fn as_unknown(&self) -> &IUnknown {
+    /// Converts to a `ComPtr<IUnknown>` without calling `QueryInterface`, unlike `try_cast`.
+    /// Every COM interface inherits from `IUnknown`, so its vtable always begins with the
+    /// `IUnknown` methods and any interface pointer is also a valid `IUnknown` pointer.
+    /// The result is not necessarily the identity pointer of the object, so use
+    /// `is_same_object` rather than comparing these.
+    pub fn into_unknown(self) -> ComPtr<IUnknown>
+    where
+        T: Interface,
+    {
+        unsafe { ComPtr::from_raw(self.into_raw() as *mut IUnknown) }
+    }
+    /// Borrows the pointer as `IUnknown`, which is sound for the same reason as `into_unknown`.
+    pub fn as_unknown(&self) -> &IUnknown {
         unsafe { &*(self.as_raw() as *mut IUnknown) }
     }
     /// Performs QueryInterface fun.
+    #[deprecated(note = "use `try_cast` or `cast_opt` which return a typed error")]
     pub fn cast<U>(&self) -> Result<ComPtr<U>, i32>
+    where
+        U: Interface,
+    {
+        self.try_cast().map_err(|err| err.to_hresult())
+    }
+    /// Performs QueryInterface fun.
+    pub fn try_cast<U>(&self) -> Result<ComPtr<U>, Error>
     where
         U: Interface,
     {
         let mut obj = null_mut();
         let err = unsafe { self.as_unknown().QueryInterface(&U::uuidof(), &mut obj) };
         if err < 0 {
-            return Err(err);
+            return Err(Error::from_hresult(err));
         }
         Ok(unsafe { ComPtr::from_raw(obj as *mut U) })
     }
+    /// Performs QueryInterface fun, returning `None` if the object does not implement `U`.
+    /// Any failure other than `E_NOINTERFACE` is still returned as an error.
+    pub fn cast_opt<U>(&self) -> Result<Option<ComPtr<U>>, Error>
+    where
+        U: Interface,
+    {
+        match self.try_cast() {
+            Ok(com) => Ok(Some(com)),
+            Err(err) if err.to_hresult() == E_NOINTERFACE => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+    /// Checks whether both pointers refer to the same COM object, by comparing the pointers
+    /// obtained by querying both for `IUnknown` as the COM spec requires for identity.
+    /// Unlike `==` this works across different interfaces of the same object.
+    pub fn is_same_object<U>(&self, other: &ComPtr<U>) -> bool {
+        match (self.identity(), other.identity()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        }
+    }
+    /// Leaks the reference, like `Box::leak`, for objects which should live for the rest of the
+    /// process such as global singletons.
+    /// __The reference is never released__, so the object will never be destroyed unless
+    /// something else releases it, in which case the returned reference dangles.
+    pub fn leak(self) -> &'static T {
+        unsafe { &*self.into_raw() }
+    }
+    /// For internal use only.
+    /// The identity pointer is only meaningful while the object is alive.
+    fn identity(&self) -> Result<*mut IUnknown, Error> {
+        self.try_cast::<IUnknown>().map(|unk| unk.as_raw())
+    }
+    /// Obtains a mutable reference for interface methods which winapi binds as taking `&mut self`.
+    /// # Safety
+    /// COM objects are reference counted and shared, so other references to the same object may
+    /// exist through clones of this `ComPtr` or elsewhere. The caller must ensure the returned
+    /// reference is not aliased while it is in use.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn as_mut(&self) -> &mut T {
+        &mut *self.as_raw()
+    }
     /// Obtains the raw pointer without transferring ownership.
     /// Do __not__ release this pointer because it is still owned by the `ComPtr`.
     pub fn as_raw(&self) -> *mut T {
@@ -123,6 +235,8 @@ impl<T> Drop for ComPtr<T> {
         }
     }
 }
+/// Compares the interface pointers directly.
+/// Use `is_same_object` to check for COM identity instead.
 impl<T> PartialEq<ComPtr<T>> for ComPtr<T>
 where
     T: Interface,
@@ -131,3 +245,497 @@ where
         self.0 == other.0
     }
 }
+impl<T> Eq for ComPtr<T> where T: Interface {}
+/// Hashes the COM identity of the object, so pointers to the same object hash the same even when
+/// they are different interfaces, consistent with both `==` and `is_same_object`.
+/// This calls `QueryInterface` every time, so it is not free, and the hash is only stable for as
+/// long as the object is alive.
+impl<T> Hash for ComPtr<T>
+where
+    T: Interface,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let identity = self.identity().unwrap_or(self.as_raw() as *mut IUnknown);
+        identity.hash(state)
+    }
+}
+/// Gets the class factory for `clsid` with `CoGetClassObject`, where `ctx` is a combination of
+/// `CLSCTX_*` flags. `F` is usually `IClassFactory`.
+pub fn get_class_factory<F>(clsid: &GUID, ctx: DWORD) -> Result<ComPtr<F>, Error>
+where
+    F: Interface,
+{
+    let com = unsafe {
+        ComPtr::from_fn(|ptr| {
+            let hr = CoGetClassObject(
+                clsid,
+                ctx,
+                null_mut(),
+                &F::uuidof(),
+                ptr as *mut _ as *mut _,
+            );
+            if hr < 0 {
+                return Err(Error::from_hresult(hr));
+            }
+            Ok(())
+        })
+    }?;
+    com.ok_or_else(|| Error::from_hresult(E_POINTER))
+}
+/// Creates a single object of the class `clsid` with `CoCreateInstance`, where `ctx` is a
+/// combination of `CLSCTX_*` flags.
+pub fn create_instance<T>(clsid: &GUID, ctx: DWORD) -> Result<ComPtr<T>, Error>
+where
+    T: Interface,
+{
+    let com = unsafe {
+        ComPtr::from_fn(|ptr| {
+            let hr = CoCreateInstance(
+                clsid,
+                null_mut(),
+                ctx,
+                &T::uuidof(),
+                ptr as *mut _ as *mut _,
+            );
+            if hr < 0 {
+                return Err(Error::from_hresult(hr));
+            }
+            Ok(())
+        })
+    }?;
+    com.ok_or_else(|| Error::from_hresult(E_POINTER))
+}
+/// Keeps COM initialized on the current thread, calling `CoUninitialize` when dropped.
+/// This is not `Send` because COM has to be uninitialized on the same thread it was initialized
+/// on, and apartments are thread affine.
+pub struct ComApartment(PhantomData<*mut ()>);
+impl ComApartment {
+    /// Initializes COM on the current thread in the multithreaded apartment.
+    pub fn init_mta() -> Result<ComApartment, Error> {
+        ComApartment::init(COINIT_MULTITHREADED)
+    }
+    /// Initializes COM on the current thread in a single threaded apartment.
+    pub fn init_sta() -> Result<ComApartment, Error> {
+        ComApartment::init(COINIT_APARTMENTTHREADED)
+    }
+    fn init(coinit: DWORD) -> Result<ComApartment, Error> {
+        // S_FALSE means COM was already initialized on this thread, which still has to be
+        // balanced by a call to CoUninitialize
+        let hr = unsafe { CoInitializeEx(null_mut(), coinit) };
+        if hr < 0 {
+            return Err(Error::from_hresult(hr));
+        }
+        Ok(ComApartment(PhantomData))
+    }
+}
+impl Debug for ComApartment {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        f.write_str("ComApartment")
+    }
+}
+impl Drop for ComApartment {
+    fn drop(&mut self) {
+        unsafe { CoUninitialize() };
+    }
+}
+/// Implemented for `IEnum*` style interfaces so they can be iterated with `ComEnumerator`.
+pub unsafe trait ComEnum: Interface {
+    /// The element type written by `Next`.
+    type Raw;
+    /// The element type yielded by the iterator.
+    type Item;
+    /// Calls `Next` requesting a single element.
+    unsafe fn next_raw(&self, raw: *mut Self::Raw, fetched: *mut ULONG) -> HRESULT;
+    /// Takes ownership of an element fetched by `next_raw`.
+    unsafe fn from_raw(raw: Self::Raw) -> Self::Item;
+}
+unsafe impl ComEnum for IEnumUnknown {
+    type Raw = *mut IUnknown;
+    type Item = ComPtr<IUnknown>;
+    unsafe fn next_raw(&self, raw: *mut *mut IUnknown, fetched: *mut ULONG) -> HRESULT {
+        self.Next(1, raw, fetched)
+    }
+    unsafe fn from_raw(raw: *mut IUnknown) -> ComPtr<IUnknown> {
+        ComPtr::from_raw(raw)
+    }
+}
+unsafe impl ComEnum for IEnumString {
+    type Raw = LPOLESTR;
+    type Item = OsString;
+    unsafe fn next_raw(&self, raw: *mut LPOLESTR, fetched: *mut ULONG) -> HRESULT {
+        self.Next(1, raw, fetched)
+    }
+    unsafe fn from_raw(raw: LPOLESTR) -> OsString {
+        let s = OsString::from_wide_ptr_null(raw);
+        CoTaskMemFree(raw.cast());
+        s
+    }
+}
+/// Iterates over the elements of an `IEnum*` style interface by calling `Next` for one element
+/// at a time. Iteration stops when `Next` returns `S_FALSE` or fetches nothing, or after the
+/// first error.
+pub struct ComEnumerator<E> {
+    inner: ComPtr<E>,
+    done: bool,
+}
+impl<E> ComEnumerator<E>
+where
+    E: ComEnum,
+{
+    pub fn new(inner: ComPtr<E>) -> ComEnumerator<E> {
+        ComEnumerator { inner, done: false }
+    }
+    /// The underlying enumerator, for calling `Reset`, `Skip`, or `Clone`.
+    pub fn inner(&self) -> &ComPtr<E> {
+        &self.inner
+    }
+    pub fn into_inner(self) -> ComPtr<E> {
+        self.inner
+    }
+}
+impl<E> Iterator for ComEnumerator<E>
+where
+    E: ComEnum,
+{
+    type Item = Result<E::Item, Error>;
+    fn next(&mut self) -> Option<Result<E::Item, Error>> {
+        if self.done {
+            return None;
+        }
+        let mut raw = MaybeUninit::uninit();
+        let mut fetched = 0;
+        let hr = unsafe { self.inner.next_raw(raw.as_mut_ptr(), &mut fetched) };
+        if hr < 0 {
+            self.done = true;
+            return Some(Err(Error::from_hresult(hr)));
+        }
+        if hr == S_FALSE || fetched == 0 {
+            self.done = true;
+            return None;
+        }
+        Some(Ok(unsafe { E::from_raw(raw.assume_init()) }))
+    }
+}
+impl<E> Debug for ComEnumerator<E> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        f.debug_struct("ComEnumerator")
+            .field("inner", &self.inner)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+/// An owned `VARIANT`, which is cleared with `VariantClear` on drop, releasing whatever it holds.
+pub struct Variant(VARIANT);
+impl Variant {
+    /// Creates a `VT_EMPTY` variant.
+    pub fn new() -> Variant {
+        unsafe {
+            let mut raw = zeroed();
+            VariantInit(&mut raw);
+            Variant(raw)
+        }
+    }
+    /// Takes ownership of a raw `VARIANT`, which will be cleared on drop.
+    pub unsafe fn from_raw(raw: VARIANT) -> Variant {
+        Variant(raw)
+    }
+    pub fn from_i32(value: i32) -> Variant {
+        let mut variant = Variant::new();
+        unsafe {
+            let n2 = variant.0.n1.n2_mut();
+            n2.vt = VT_I4 as VARTYPE;
+            *n2.n3.lVal_mut() = value;
+        }
+        variant
+    }
+    pub fn from_i64(value: i64) -> Variant {
+        let mut variant = Variant::new();
+        unsafe {
+            let n2 = variant.0.n1.n2_mut();
+            n2.vt = VT_I8 as VARTYPE;
+            *n2.n3.llVal_mut() = value;
+        }
+        variant
+    }
+    pub fn from_f64(value: f64) -> Variant {
+        let mut variant = Variant::new();
+        unsafe {
+            let n2 = variant.0.n1.n2_mut();
+            n2.vt = VT_R8 as VARTYPE;
+            *n2.n3.dblVal_mut() = value;
+        }
+        variant
+    }
+    pub fn from_bool(value: bool) -> Variant {
+        let mut variant = Variant::new();
+        unsafe {
+            let n2 = variant.0.n1.n2_mut();
+            n2.vt = VT_BOOL as VARTYPE;
+            *n2.n3.boolVal_mut() = if value { VARIANT_TRUE } else { VARIANT_FALSE };
+        }
+        variant
+    }
+    /// The variant takes ownership of the string.
+    pub fn from_bstr(value: BStr) -> Variant {
+        let mut variant = Variant::new();
+        unsafe {
+            let n2 = variant.0.n1.n2_mut();
+            n2.vt = VT_BSTR as VARTYPE;
+            *n2.n3.bstrVal_mut() = value.into_raw();
+        }
+        variant
+    }
+    /// The variant takes ownership of the reference.
+    pub fn from_unknown(value: ComPtr<IUnknown>) -> Variant {
+        let mut variant = Variant::new();
+        unsafe {
+            let n2 = variant.0.n1.n2_mut();
+            n2.vt = VT_UNKNOWN as VARTYPE;
+            *n2.n3.punkVal_mut() = value.into_raw();
+        }
+        variant
+    }
+    /// The `VT_*` type of the value currently held.
+    pub fn vt(&self) -> VARTYPE {
+        unsafe { self.0.n1.n2().vt }
+    }
+    fn is(&self, vt: VARENUM) -> bool {
+        self.vt() == vt as VARTYPE
+    }
+    pub fn as_i32(&self) -> Option<i32> {
+        if !self.is(VT_I4) {
+            return None;
+        }
+        Some(unsafe { *self.0.n1.n2().n3.lVal() })
+    }
+    pub fn as_i64(&self) -> Option<i64> {
+        if !self.is(VT_I8) {
+            return None;
+        }
+        Some(unsafe { *self.0.n1.n2().n3.llVal() })
+    }
+    pub fn as_f64(&self) -> Option<f64> {
+        if !self.is(VT_R8) {
+            return None;
+        }
+        Some(unsafe { *self.0.n1.n2().n3.dblVal() })
+    }
+    pub fn as_bool(&self) -> Option<bool> {
+        if !self.is(VT_BOOL) {
+            return None;
+        }
+        Some(unsafe { *self.0.n1.n2().n3.boolVal() } != VARIANT_FALSE)
+    }
+    pub fn as_bstr(&self) -> Option<&BStr> {
+        if !self.is(VT_BSTR) {
+            return None;
+        }
+        Some(unsafe { BStr::from_raw_ref(self.0.n1.n2().n3.bstrVal()) })
+    }
+    /// Returns a new reference to the object held by a `VT_UNKNOWN` or `VT_DISPATCH` variant.
+    /// A variant holding a null object returns `None` as well.
+    pub fn as_unknown(&self) -> Option<ComPtr<IUnknown>> {
+        if !self.is(VT_UNKNOWN) && !self.is(VT_DISPATCH) {
+            return None;
+        }
+        // IDispatch inherits from IUnknown so both can be read through punkVal
+        unsafe { ComPtr::from_raw_add_ref_opt(*self.0.n1.n2().n3.punkVal()) }
+    }
+    /// Converts the value to another `VT_*` type with `VariantChangeType`, leaving this variant
+    /// untouched.
+    pub fn change_type(&self, vt: VARTYPE) -> Result<Variant, Error> {
+        let mut variant = Variant::new();
+        let hr = unsafe { VariantChangeType(&mut variant.0, &self.0, 0, vt) };
+        if hr < 0 {
+            return Err(Error::from_hresult(hr));
+        }
+        Ok(variant)
+    }
+    /// Copies the variant with `VariantCopy`, which copies strings and adds references to objects.
+    pub fn try_clone(&self) -> Result<Variant, Error> {
+        let mut variant = Variant::new();
+        let hr = unsafe { VariantCopy(&mut variant.0, &self.0) };
+        if hr < 0 {
+            return Err(Error::from_hresult(hr));
+        }
+        Ok(variant)
+    }
+    /// Obtains the raw pointer without transferring ownership, for passing in-parameters.
+    pub fn as_ptr(&self) -> *const VARIANT {
+        &self.0
+    }
+    /// Obtains the raw pointer without transferring ownership, for passing out-parameters.
+    /// Any value previously held must be cleared first, as the callee will overwrite it.
+    pub fn as_mut_ptr(&mut self) -> *mut VARIANT {
+        &mut self.0
+    }
+    /// Extracts the raw `VARIANT`.
+    /// You are now responsible for clearing it yourself.
+    pub fn into_raw(self) -> VARIANT {
+        let raw = unsafe { read(&self.0) };
+        forget(self);
+        raw
+    }
+}
+impl Default for Variant {
+    fn default() -> Variant {
+        Variant::new()
+    }
+}
+impl Debug for Variant {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        f.debug_struct("Variant").field("vt", &self.vt()).finish()
+    }
+}
+impl Drop for Variant {
+    fn drop(&mut self) {
+        unsafe {
+            VariantClear(&mut self.0);
+        }
+    }
+}
+/// Implemented for plain element types which can be stored in a `SafeArray` created by
+/// `SafeArray::from_slice`.
+pub unsafe trait SafeArrayElement: Copy {
+    /// The `VT_*` type of the element.
+    const VT: VARENUM;
+}
+macro_rules! safe_array_element {
+    ($($t:ty => $vt:ident,)*) => {$(
+        unsafe impl SafeArrayElement for $t {
+            const VT: VARENUM = $vt;
+        }
+    )*};
+}
+safe_array_element! {
+    i8 => VT_I1,
+    u8 => VT_UI1,
+    i16 => VT_I2,
+    u16 => VT_UI2,
+    i32 => VT_I4,
+    u32 => VT_UI4,
+    i64 => VT_I8,
+    u64 => VT_UI8,
+    f32 => VT_R4,
+    f64 => VT_R8,
+}
+/// An owned one dimensional `SAFEARRAY` of `T`, which is destroyed with `SafeArrayDestroy` on
+/// drop.
+pub struct SafeArray<T>(NonNull<SAFEARRAY>, PhantomData<T>);
+impl<T> SafeArray<T> {
+    /// Takes ownership of a raw `SAFEARRAY`.
+    /// The array must be one dimensional and its elements must be of type `T`.
+    /// The raw pointer must not be null or this function will panic.
+    pub unsafe fn from_raw(ptr: *mut SAFEARRAY) -> SafeArray<T> {
+        SafeArray(
+            NonNull::new(ptr).expect("ptr should not be null"),
+            PhantomData,
+        )
+    }
+    /// Creates an array with a lower bound of 0 by copying the elements of the slice.
+    pub fn from_slice(slice: &[T]) -> Result<SafeArray<T>, Error>
+    where
+        T: SafeArrayElement,
+    {
+        let len = ULONG::try_from(slice.len()).map_err(|_| Error::from_hresult(E_INVALIDARG))?;
+        let ptr = unsafe { SafeArrayCreateVector(T::VT as VARTYPE, 0, len) };
+        let array: SafeArray<T> = match NonNull::new(ptr) {
+            Some(ptr) => SafeArray(ptr, PhantomData),
+            None => return Err(Error::from_hresult(E_OUTOFMEMORY)),
+        };
+        {
+            let guard = array.lock()?;
+            unsafe {
+                guard
+                    .data
+                    .as_ptr()
+                    .copy_from_nonoverlapping(slice.as_ptr(), slice.len())
+            };
+        }
+        Ok(array)
+    }
+    /// The number of elements in the array.
+    pub fn len(&self) -> Result<usize, Error> {
+        let (mut lower, mut upper): (LONG, LONG) = (0, 0);
+        unsafe {
+            let hr = SafeArrayGetLBound(self.as_raw(), 1, &mut lower);
+            if hr < 0 {
+                return Err(Error::from_hresult(hr));
+            }
+            let hr = SafeArrayGetUBound(self.as_raw(), 1, &mut upper);
+            if hr < 0 {
+                return Err(Error::from_hresult(hr));
+            }
+        }
+        // An empty array has an upper bound one less than its lower bound
+        Ok((i64::from(upper) - i64::from(lower) + 1) as usize)
+    }
+    /// Locks the array with `SafeArrayAccessData` for access to its elements, unlocking it when
+    /// the guard is dropped.
+    /// Fails with `E_INVALIDARG` if the array is not one dimensional, or `DISP_E_BADVARTYPE` if
+    /// its elements are not the size of `T`, since the elements could not be viewed as `[T]`.
+    pub fn lock(&self) -> Result<SafeArrayGuard<'_, T>, Error> {
+        // winapi has no SafeArrayGetDim or SafeArrayGetElemsize, but they only read these fields
+        let (dims, elem_size) = unsafe { (self.0.as_ref().cDims, self.0.as_ref().cbElements) };
+        if dims != 1 {
+            return Err(Error::from_hresult(E_INVALIDARG));
+        }
+        if elem_size as usize != size_of::<T>() {
+            return Err(Error::from_hresult(DISP_E_BADVARTYPE));
+        }
+        let len = self.len()?;
+        let mut data = null_mut();
+        let hr = unsafe { SafeArrayAccessData(self.as_raw(), &mut data) };
+        if hr < 0 {
+            return Err(Error::from_hresult(hr));
+        }
+        let data = NonNull::new(data.cast()).unwrap_or(NonNull::dangling());
+        Ok(SafeArrayGuard {
+            array: self,
+            data,
+            len,
+        })
+    }
+    /// Obtains the raw pointer without transferring ownership.
+    pub fn as_raw(&self) -> *mut SAFEARRAY {
+        self.0.as_ptr()
+    }
+    /// Extracts the raw pointer.
+    /// You are now responsible for destroying it yourself.
+    pub fn into_raw(self) -> *mut SAFEARRAY {
+        let p = self.0.as_ptr();
+        forget(self);
+        p
+    }
+}
+impl<T> Debug for SafeArray<T> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        write!(f, "{:?}", self.0)
+    }
+}
+impl<T> Drop for SafeArray<T> {
+    fn drop(&mut self) {
+        unsafe {
+            SafeArrayDestroy(self.as_raw());
+        }
+    }
+}
+/// Keeps a `SafeArray` locked while its elements are borrowed.
+pub struct SafeArrayGuard<'a, T> {
+    array: &'a SafeArray<T>,
+    data: NonNull<T>,
+    len: usize,
+}
+impl<'a, T> Deref for SafeArrayGuard<'a, T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        unsafe { from_raw_parts(self.data.as_ptr(), self.len) }
+    }
+}
+impl<'a, T> Drop for SafeArrayGuard<'a, T> {
+    fn drop(&mut self) {
+        unsafe {
+            SafeArrayUnaccessData(self.array.as_raw());
+        }
+    }
+}
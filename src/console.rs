@@ -6,33 +6,89 @@
 use error::{Error, Result};
 use handle::Handle;
 use std::{
+    ffi::{OsStr, OsString},
     mem::{size_of_val, zeroed},
+    ops::{BitAnd, BitOr, Not},
     os::windows::io::FromRawHandle,
+    panic::{catch_unwind, AssertUnwindSafe},
     ptr::{null, null_mut},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
 };
-use wide::ToWide;
+use wide::{char_to_wide, FromWide, ToWide};
 use winapi::{
-    shared::minwindef::{DWORD, FALSE},
+    shared::{
+        minwindef::{BOOL, DWORD, FALSE, TRUE},
+        windef::HWND,
+        winerror::ERROR_INVALID_PARAMETER,
+    },
     um::{
         consoleapi::{
-            AllocConsole, GetConsoleCP, GetConsoleOutputCP, GetNumberOfConsoleInputEvents,
-            ReadConsoleInputW,
+            AllocConsole, GetConsoleCP, GetConsoleMode, GetConsoleOutputCP,
+            GetNumberOfConsoleInputEvents, ReadConsoleInputW, ReadConsoleW, SetConsoleCtrlHandler,
+            SetConsoleMode,
         },
+        errhandlingapi::SetLastError,
         fileapi::{CreateFileW, OPEN_EXISTING},
         handleapi::INVALID_HANDLE_VALUE,
+        processenv::GetStdHandle,
+        winbase::STD_OUTPUT_HANDLE,
         wincon::{
-            AttachConsole, CreateConsoleScreenBuffer, FlushConsoleInputBuffer, FreeConsole,
-            GetConsoleScreenBufferInfo, GetConsoleScreenBufferInfoEx, GetCurrentConsoleFont,
-            SetConsoleActiveScreenBuffer, SetConsoleCP, SetConsoleOutputCP,
-            SetConsoleScreenBufferInfoEx, WriteConsoleOutputW, CHAR_INFO, CONSOLE_FONT_INFOEX,
+            AttachConsole, CreateConsoleScreenBuffer, FillConsoleOutputAttribute,
+            FillConsoleOutputCharacterW, FlushConsoleInputBuffer, FreeConsole,
+            GenerateConsoleCtrlEvent, GetConsoleCursorInfo, GetConsoleScreenBufferInfo,
+            GetConsoleScreenBufferInfoEx, GetConsoleTitleW, GetConsoleWindow,
+            GetCurrentConsoleFont, GetCurrentConsoleFontEx, GetLargestConsoleWindowSize,
+            ReadConsoleOutputW, ScrollConsoleScreenBufferW, SetConsoleActiveScreenBuffer,
+            SetConsoleCP, SetConsoleCursorInfo, SetConsoleCursorPosition, SetConsoleOutputCP,
+            SetConsoleScreenBufferInfoEx, SetConsoleScreenBufferSize, SetConsoleTextAttribute,
+            SetConsoleTitleW, SetConsoleWindowInfo, SetCurrentConsoleFontEx, WriteConsoleInputW,
+            WriteConsoleOutputW, BACKGROUND_BLUE, BACKGROUND_GREEN, BACKGROUND_INTENSITY,
+            BACKGROUND_RED, CHAR_INFO, CONSOLE_CURSOR_INFO, CONSOLE_FONT_INFOEX,
             CONSOLE_SCREEN_BUFFER_INFO, CONSOLE_SCREEN_BUFFER_INFOEX, CONSOLE_TEXTMODE_BUFFER,
-            COORD, FOCUS_EVENT, INPUT_RECORD, KEY_EVENT, MENU_EVENT, MOUSE_EVENT, SMALL_RECT,
+            COORD, CTRL_BREAK_EVENT, CTRL_CLOSE_EVENT, CTRL_C_EVENT, CTRL_LOGOFF_EVENT,
+            CTRL_SHUTDOWN_EVENT, DISABLE_NEWLINE_AUTO_RETURN, DOUBLE_CLICK, ENABLE_AUTO_POSITION,
+            ENABLE_ECHO_INPUT, ENABLE_EXTENDED_FLAGS, ENABLE_INSERT_MODE, ENABLE_LINE_INPUT,
+            ENABLE_LVB_GRID_WORLDWIDE, ENABLE_MOUSE_INPUT, ENABLE_PROCESSED_INPUT,
+            ENABLE_PROCESSED_OUTPUT, ENABLE_QUICK_EDIT_MODE, ENABLE_VIRTUAL_TERMINAL_INPUT,
+            ENABLE_VIRTUAL_TERMINAL_PROCESSING, ENABLE_WINDOW_INPUT, ENABLE_WRAP_AT_EOL_OUTPUT,
+            FOCUS_EVENT, FOREGROUND_BLUE, FOREGROUND_GREEN, FOREGROUND_INTENSITY, FOREGROUND_RED,
+            FROM_LEFT_1ST_BUTTON_PRESSED, INPUT_RECORD, KEY_EVENT, MENU_EVENT, MOUSE_EVENT,
+            MOUSE_HWHEELED, MOUSE_MOVED, MOUSE_WHEELED, RIGHTMOST_BUTTON_PRESSED, SMALL_RECT,
             WINDOW_BUFFER_SIZE_EVENT,
         },
         winnt::{FILE_SHARE_READ, FILE_SHARE_WRITE, GENERIC_READ, GENERIC_WRITE, HANDLE},
     },
 };
 
+/// A rectangle of cells as `(left, top, right, bottom)`, with exclusive right and bottom edges
+pub type Rect = (i16, i16, i16, i16);
+
+/// The number of cells in a rectangle of `size`, failing with `ERROR_INVALID_PARAMETER` if
+/// either dimension is negative.
+fn cell_count(size: (i16, i16)) -> Result<usize> {
+    if size.0 < 0 || size.1 < 0 {
+        return Err(Error::new(ERROR_INVALID_PARAMETER));
+    }
+    // Both are at most i16::MAX so the product fits in an i32
+    Ok((i32::from(size.0) * i32::from(size.1)) as usize)
+}
+/// Converts a rectangle into the `SMALL_RECT` the API wants, which has inclusive right and
+/// bottom edges, failing with `ERROR_INVALID_PARAMETER` if either edge is `i16::MIN`.
+fn inclusive_rect(rect: Rect) -> Result<SMALL_RECT> {
+    match (rect.2.checked_sub(1), rect.3.checked_sub(1)) {
+        (Some(right), Some(bottom)) => Ok(SMALL_RECT {
+            Left: rect.0,
+            Top: rect.1,
+            Right: right,
+            Bottom: bottom,
+        }),
+        _ => Err(Error::new(ERROR_INVALID_PARAMETER)),
+    }
+}
+
 pub struct ScreenBuffer(Handle);
 impl ScreenBuffer {
     pub fn new() -> Result<ScreenBuffer> {
@@ -102,22 +158,37 @@ impl ScreenBuffer {
         }
         Ok(())
     }
-    // pub fn font_ex(&self) -> Result<FontEx> {
-    // unsafe {
-    // let mut info = zeroed();
-    // info.cbSize = size_of_val(&info);
-    // let res = GetCurrentConsoleFontEx(*self.0, w::FALSE, &mut info);
-    // if res == 0 { return Error::last() }
-    // Ok(FontEx(info))
-    // }
-    // }
+    /// The font currently used by the buffer
+    pub fn font_ex(&self) -> Result<FontInfoEx> {
+        let mut info: CONSOLE_FONT_INFOEX = unsafe { zeroed() };
+        info.cbSize = size_of_val(&info) as u32;
+        let res = unsafe { GetCurrentConsoleFontEx(*self.0, FALSE, &mut info) };
+        if res == 0 {
+            return Error::last_result();
+        }
+        Ok(FontInfoEx(info))
+    }
+    pub fn set_font_ex(&self, font: &FontInfoEx) -> Result<()> {
+        let mut info = font.0;
+        info.cbSize = size_of_val(&info) as u32;
+        let res = unsafe { SetCurrentConsoleFontEx(*self.0, FALSE, &mut info) };
+        if res == 0 {
+            return Error::last_result();
+        }
+        Ok(())
+    }
+    /// Writes a rectangle of `size` cells with its top left corner at `pos`.
+    /// Fails with `ERROR_INVALID_PARAMETER` if `size` is negative or `buf` does not hold exactly
+    /// `size.0 * size.1` cells.
     pub fn write_output(&self, buf: &[CharInfo], size: (i16, i16), pos: (i16, i16)) -> Result<()> {
-        assert!(buf.len() == (size.0 as usize) * (size.1 as usize));
+        if buf.len() != cell_count(size)? {
+            return Err(Error::new(ERROR_INVALID_PARAMETER));
+        }
         let mut rect = SMALL_RECT {
             Left: pos.0,
             Top: pos.1,
-            Right: pos.0 + size.0,
-            Bottom: pos.1 + size.1,
+            Right: pos.0.saturating_add(size.0),
+            Bottom: pos.1.saturating_add(size.1),
         };
         let size = COORD {
             X: size.0,
@@ -138,6 +209,235 @@ impl ScreenBuffer {
         }
         Ok(())
     }
+    /// Reads a rectangle of cells, the counterpart to `write_output`.
+    /// The returned buffer always has `size.0 * size.1` cells, but if the rectangle extends past
+    /// the edge of the screen buffer it is clamped and the cells outside of it are left zeroed.
+    /// The rectangle which was actually read is returned alongside, and its cells are at the
+    /// start of each row of the buffer.
+    /// Fails with `ERROR_INVALID_PARAMETER` if `size` is negative.
+    pub fn read_output(&self, size: (i16, i16), pos: (i16, i16)) -> Result<(Vec<CharInfo>, Rect)> {
+        let mut buf = vec![CharInfo::new(0, 0); cell_count(size)?];
+        let mut rect = SMALL_RECT {
+            Left: pos.0,
+            Top: pos.1,
+            Right: pos.0.saturating_add(size.0),
+            Bottom: pos.1.saturating_add(size.1),
+        };
+        let size = COORD {
+            X: size.0,
+            Y: size.1,
+        };
+        let pos = COORD { X: 0, Y: 0 };
+        let res = unsafe {
+            ReadConsoleOutputW(
+                *self.0,
+                buf.as_mut_ptr() as *mut CHAR_INFO,
+                size,
+                pos,
+                &mut rect,
+            )
+        };
+        if res == 0 {
+            return Error::last_result();
+        }
+        // The API reports the rectangle with inclusive edges
+        let rect = (rect.Left, rect.Top, rect.Right + 1, rect.Bottom + 1);
+        Ok((buf, rect))
+    }
+    pub fn cursor_position(&self) -> Result<(i16, i16)> {
+        Ok(self.info()?.cursor_position())
+    }
+    pub fn set_cursor_position(&self, pos: (i16, i16)) -> Result<()> {
+        let pos = COORD { X: pos.0, Y: pos.1 };
+        let res = unsafe { SetConsoleCursorPosition(*self.0, pos) };
+        if res == 0 {
+            return Error::last_result();
+        }
+        Ok(())
+    }
+    pub fn cursor_info(&self) -> Result<CursorInfo> {
+        let mut info: CONSOLE_CURSOR_INFO = unsafe { zeroed() };
+        let res = unsafe { GetConsoleCursorInfo(*self.0, &mut info) };
+        if res == 0 {
+            return Error::last_result();
+        }
+        Ok(CursorInfo {
+            size: info.dwSize,
+            visible: info.bVisible != 0,
+        })
+    }
+    /// Fails with `ERROR_INVALID_PARAMETER` if the size is not between 1 and 100.
+    pub fn set_cursor_info(&self, info: CursorInfo) -> Result<()> {
+        if info.size < 1 || info.size > 100 {
+            return Err(Error::new(ERROR_INVALID_PARAMETER));
+        }
+        let info = CONSOLE_CURSOR_INFO {
+            dwSize: info.size,
+            bVisible: info.visible as BOOL,
+        };
+        let res = unsafe { SetConsoleCursorInfo(*self.0, &info) };
+        if res == 0 {
+            return Error::last_result();
+        }
+        Ok(())
+    }
+    pub fn hide_cursor(&self) -> Result<()> {
+        self.set_cursor_visible(false)
+    }
+    pub fn show_cursor(&self) -> Result<()> {
+        self.set_cursor_visible(true)
+    }
+    fn set_cursor_visible(&self, visible: bool) -> Result<()> {
+        let mut info = self.cursor_info()?;
+        info.visible = visible;
+        self.set_cursor_info(info)
+    }
+    /// The attributes that text written to the buffer is given
+    pub fn text_attribute(&self) -> Result<u16> {
+        Ok(self.info()?.attributes())
+    }
+    /// Sets the attributes that text written to the buffer is given, such as a `Color`
+    pub fn set_text_attribute(&self, attr: u16) -> Result<()> {
+        let res = unsafe { SetConsoleTextAttribute(*self.0, attr) };
+        if res == 0 {
+            return Error::last_result();
+        }
+        Ok(())
+    }
+    /// Resizes the buffer, in character cells.
+    /// The buffer can never be smaller than the console window, so to shrink the buffer below
+    /// the current window size the window has to be shrunk first.
+    pub fn set_size(&self, size: (i16, i16)) -> Result<()> {
+        let size = COORD {
+            X: size.0,
+            Y: size.1,
+        };
+        let res = unsafe { SetConsoleScreenBufferSize(*self.0, size) };
+        if res == 0 {
+            return Error::last_result();
+        }
+        Ok(())
+    }
+    /// The largest window size, in character cells, that fits on the display with the current
+    /// font.
+    pub fn largest_window_size(&self) -> Result<(i16, i16)> {
+        let size = unsafe { GetLargestConsoleWindowSize(*self.0) };
+        if size.X == 0 && size.Y == 0 {
+            return Error::last_result();
+        }
+        Ok((size.X, size.Y))
+    }
+    /// Moves or resizes the visible window within the buffer.
+    /// `rect` is `(left, top, right, bottom)`. When `absolute` is true these are buffer
+    /// coordinates with an exclusive right and bottom edge, matching the window rect from
+    /// `info_ex`. Otherwise they are offsets added to the edges of the current window.
+    /// Fails with `ERROR_INVALID_PARAMETER` if an absolute right or bottom edge is `i16::MIN`.
+    pub fn set_window(&self, absolute: bool, rect: (i16, i16, i16, i16)) -> Result<()> {
+        let rect = if absolute {
+            inclusive_rect(rect)?
+        } else {
+            SMALL_RECT {
+                Left: rect.0,
+                Top: rect.1,
+                Right: rect.2,
+                Bottom: rect.3,
+            }
+        };
+        let res = unsafe { SetConsoleWindowInfo(*self.0, absolute as BOOL, &rect) };
+        if res == 0 {
+            return Error::last_result();
+        }
+        Ok(())
+    }
+    /// Moves the cells in `scroll_rect` so its top left corner is at `dest`, filling the cells
+    /// left behind with `fill`. Only cells within `clip_rect` are changed, if one is given.
+    /// Rectangles are `(left, top, right, bottom)` with an exclusive right and bottom edge, like
+    /// `set_window`, so a right or bottom edge of `i16::MIN` fails with `ERROR_INVALID_PARAMETER`.
+    pub fn scroll(
+        &self,
+        scroll_rect: (i16, i16, i16, i16),
+        clip_rect: Option<(i16, i16, i16, i16)>,
+        dest: (i16, i16),
+        fill: CharInfo,
+    ) -> Result<()> {
+        let scroll_rect = inclusive_rect(scroll_rect)?;
+        let clip_rect = match clip_rect {
+            Some(rect) => Some(inclusive_rect(rect)?),
+            None => None,
+        };
+        let res = unsafe {
+            ScrollConsoleScreenBufferW(
+                *self.0,
+                &scroll_rect,
+                clip_rect.as_ref().map(|x| x as *const _).unwrap_or(null()),
+                COORD {
+                    X: dest.0,
+                    Y: dest.1,
+                },
+                &fill.0,
+            )
+        };
+        if res == 0 {
+            return Error::last_result();
+        }
+        Ok(())
+    }
+    /// Writes `ch` to `len` consecutive cells starting at `pos`, wrapping onto following rows.
+    /// Returns the number of cells actually written.
+    pub fn fill_character(&self, ch: u16, len: u32, pos: (i16, i16)) -> Result<u32> {
+        let pos = COORD { X: pos.0, Y: pos.1 };
+        let mut written = 0;
+        let res = unsafe { FillConsoleOutputCharacterW(*self.0, ch, len, pos, &mut written) };
+        if res == 0 {
+            return Error::last_result();
+        }
+        Ok(written)
+    }
+    /// Like `fill_character` but takes a `char`.
+    /// A console cell holds a single UTF-16 code unit, so characters which need a surrogate pair
+    /// fail with `ERROR_INVALID_PARAMETER` rather than being truncated.
+    pub fn fill_char(&self, ch: char, len: u32, pos: (i16, i16)) -> Result<u32> {
+        match char_to_wide(ch) {
+            (wide, 1) => self.fill_character(wide[0], len, pos),
+            _ => Err(Error::new(ERROR_INVALID_PARAMETER)),
+        }
+    }
+    /// Sets the attributes of `len` consecutive cells starting at `pos`, wrapping onto following
+    /// rows. Returns the number of cells actually written.
+    pub fn fill_attribute(&self, attr: u16, len: u32, pos: (i16, i16)) -> Result<u32> {
+        let pos = COORD { X: pos.0, Y: pos.1 };
+        let mut written = 0;
+        let res = unsafe { FillConsoleOutputAttribute(*self.0, attr, len, pos, &mut written) };
+        if res == 0 {
+            return Error::last_result();
+        }
+        Ok(written)
+    }
+    /// Fills the whole buffer with spaces in the current text attribute and moves the cursor to
+    /// the top left corner.
+    pub fn clear(&self) -> Result<()> {
+        let info = self.info()?;
+        let (width, height) = info.size();
+        let len = width as u32 * height as u32;
+        self.fill_character(b' ' as u16, len, (0, 0))?;
+        self.fill_attribute(info.attributes(), len, (0, 0))?;
+        self.set_cursor_position((0, 0))
+    }
+    /// The output mode flags such as `ConsoleMode::ENABLE_VIRTUAL_TERMINAL_PROCESSING`
+    pub fn mode(&self) -> Result<ConsoleMode> {
+        get_mode(*self.0)
+    }
+    pub fn set_mode(&self, mode: ConsoleMode) -> Result<()> {
+        set_mode(*self.0, mode)
+    }
+    /// Enables processing of ANSI escape sequences written to the buffer
+    pub fn enable_virtual_terminal(&self) -> Result<()> {
+        self.set_mode(self.mode()? | ConsoleMode::ENABLE_VIRTUAL_TERMINAL_PROCESSING)
+    }
+    /// Whether this is a real console rather than a redirected file or pipe.
+    pub fn is_console(&self) -> bool {
+        get_mode(*self.0).is_ok()
+    }
     pub fn font_size(&self) -> Result<(i16, i16)> {
         unsafe {
             let mut font = zeroed();
@@ -194,39 +494,129 @@ impl InputBuffer {
         }
         Ok(buf[..(size as usize)]
             .iter()
-            .map(|input| unsafe {
-                match input.EventType {
-                    KEY_EVENT => {
-                        let e = input.Event.KeyEvent();
-                        Input::Key {
-                            key_down: e.bKeyDown != 0,
-                            repeat_count: e.wRepeatCount,
-                            key_code: e.wVirtualKeyCode,
-                            scan_code: e.wVirtualScanCode,
-                            wide_char: *e.uChar.UnicodeChar(),
-                            control_key_state: e.dwControlKeyState,
-                        }
-                    }
-                    MOUSE_EVENT => {
-                        let e = input.Event.MouseEvent();
-                        Input::Mouse {
-                            position: (e.dwMousePosition.X, e.dwMousePosition.Y),
-                            button_state: e.dwButtonState,
-                            control_key_state: e.dwControlKeyState,
-                            event_flags: e.dwEventFlags,
-                        }
-                    }
-                    WINDOW_BUFFER_SIZE_EVENT => {
-                        let s = input.Event.WindowBufferSizeEvent().dwSize;
-                        Input::WindowBufferSize(s.X, s.Y)
-                    }
-                    MENU_EVENT => Input::Menu(input.Event.MenuEvent().dwCommandId),
-                    FOCUS_EVENT => Input::Focus(input.Event.FocusEvent().bSetFocus != 0),
-                    e => unreachable!("invalid event type: {}", e),
-                }
-            })
+            .map(|input| unsafe { Input::from_raw(input) })
             .collect())
     }
+    /// Blocks until a key is pressed and returns it, discarding any other input and key releases
+    /// read before it. Input after the key press is left in the buffer.
+    pub fn read_key(&self) -> Result<KeyEvent> {
+        loop {
+            let mut record: INPUT_RECORD = unsafe { zeroed() };
+            let mut size = 0;
+            let res = unsafe { ReadConsoleInputW(*self.0, &mut record, 1, &mut size) };
+            if res == 0 {
+                return Error::last_result();
+            }
+            if size == 0 {
+                continue;
+            }
+            if let Input::Key {
+                key_down: true,
+                repeat_count,
+                key_code,
+                scan_code,
+                wide_char,
+                control_key_state,
+            } = unsafe { Input::from_raw(&record) }
+            {
+                return Ok(KeyEvent {
+                    repeat_count,
+                    key_code,
+                    scan_code,
+                    wide_char,
+                    control_key_state,
+                });
+            }
+        }
+    }
+    /// Reads a line of text with `ReadConsoleW`, without the trailing line break.
+    /// With `ENABLE_LINE_INPUT` set this is cooked input, edited and echoed according to the
+    /// current mode; otherwise reading stops at the first carriage return or line feed.
+    /// Anything read in the same call after the line break is discarded.
+    pub fn read_line(&self) -> Result<OsString> {
+        let mut line: Vec<u16> = Vec::new();
+        let mut buf = [0u16; 0x1000];
+        loop {
+            let mut size = 0;
+            let res = unsafe {
+                ReadConsoleW(
+                    *self.0,
+                    buf.as_mut_ptr().cast(),
+                    buf.len() as DWORD,
+                    &mut size,
+                    null_mut(),
+                )
+            };
+            if res == 0 {
+                return Error::last_result();
+            }
+            let chunk = &buf[..(size as usize)];
+            match chunk.iter().position(|&c| c == 0x0D || c == 0x0A) {
+                Some(end) => {
+                    line.extend_from_slice(&chunk[..end]);
+                    break;
+                }
+                None if chunk.is_empty() => break,
+                None => line.extend_from_slice(chunk),
+            }
+        }
+        Ok(OsString::from_wide(&line))
+    }
+    /// Like `read_line`, but replaces any unpaired surrogates with U+FFFD.
+    pub fn read_line_lossy(&self) -> Result<String> {
+        Ok(self.read_line()?.to_string_lossy().into_owned())
+    }
+    /// Writes input events to the buffer as if they came from the user.
+    /// Returns the number of events actually written.
+    pub fn write_input(&self, events: &[Input]) -> Result<u32> {
+        let buf: Vec<INPUT_RECORD> = events.iter().map(|e| e.to_raw()).collect();
+        let mut size = 0;
+        let res =
+            unsafe { WriteConsoleInputW(*self.0, buf.as_ptr(), buf.len() as DWORD, &mut size) };
+        if res == 0 {
+            return Error::last_result();
+        }
+        Ok(size)
+    }
+    /// The input mode flags such as `ConsoleMode::ENABLE_MOUSE_INPUT`
+    pub fn mode(&self) -> Result<ConsoleMode> {
+        get_mode(*self.0)
+    }
+    pub fn set_mode(&self, mode: ConsoleMode) -> Result<()> {
+        set_mode(*self.0, mode)
+    }
+    /// Enables translation of user input into ANSI escape sequences
+    pub fn enable_virtual_terminal(&self) -> Result<()> {
+        self.set_mode(self.mode()? | ConsoleMode::ENABLE_VIRTUAL_TERMINAL_INPUT)
+    }
+    /// Whether this is a real console rather than a redirected file or pipe.
+    pub fn is_console(&self) -> bool {
+        get_mode(*self.0).is_ok()
+    }
+    /// Enables or disables selecting text with the mouse.
+    /// Quick edit mode is only changed when `ENABLE_EXTENDED_FLAGS` is set along with it, so that
+    /// flag is always included.
+    pub fn set_quick_edit(&self, enable: bool) -> Result<()> {
+        let mode = self.mode()? | ConsoleMode::ENABLE_EXTENDED_FLAGS;
+        let mode = if enable {
+            mode | ConsoleMode::ENABLE_QUICK_EDIT_MODE
+        } else {
+            mode & !ConsoleMode::ENABLE_QUICK_EDIT_MODE
+        };
+        self.set_mode(mode)
+    }
+    /// Enables or disables reporting mouse input as `Input::Mouse` events.
+    /// Quick edit mode takes over the mouse for selecting text, so it is disabled when enabling
+    /// mouse input, which needs `ENABLE_EXTENDED_FLAGS` like `set_quick_edit`.
+    pub fn enable_mouse(&self, enable: bool) -> Result<()> {
+        let mode = self.mode()? | ConsoleMode::ENABLE_EXTENDED_FLAGS;
+        let mode = if enable {
+            (mode | ConsoleMode::ENABLE_MOUSE_INPUT) & !ConsoleMode::ENABLE_QUICK_EDIT_MODE
+        } else {
+            mode & !ConsoleMode::ENABLE_MOUSE_INPUT
+        };
+        self.set_mode(mode)
+    }
     /// Clears all pending input
     pub fn flush_input(&self) -> Result<()> {
         let res = unsafe { FlushConsoleInputBuffer(*self.0) };
@@ -248,6 +638,23 @@ impl ScreenBufferInfo {
     pub fn size(&self) -> (i16, i16) {
         (self.0.dwSize.X, self.0.dwSize.Y)
     }
+    pub fn cursor_position(&self) -> (i16, i16) {
+        (self.0.dwCursorPosition.X, self.0.dwCursorPosition.Y)
+    }
+    /// The attributes that text written to the buffer is given
+    pub fn attributes(&self) -> u16 {
+        self.0.wAttributes
+    }
+    /// The visible window as `(left, top, right, bottom)`, with the right and bottom edges
+    /// exclusive like in `ScreenBuffer::info_ex`.
+    pub fn window(&self) -> (i16, i16, i16, i16) {
+        let r = self.0.srWindow;
+        (r.Left, r.Top, r.Right + 1, r.Bottom + 1)
+    }
+    /// The largest the window could be given the buffer size, font and display size
+    pub fn maximum_window_size(&self) -> (i16, i16) {
+        (self.0.dwMaximumWindowSize.X, self.0.dwMaximumWindowSize.Y)
+    }
 }
 #[repr(transparent)]
 #[derive(Copy, Clone)]
@@ -256,10 +663,88 @@ impl ScreenBufferInfoEx {
     pub fn raw_mut(&mut self) -> &mut CONSOLE_SCREEN_BUFFER_INFOEX {
         &mut self.0
     }
+    /// The attributes that text written to the buffer is given
+    pub fn attributes(&self) -> u16 {
+        self.0.wAttributes
+    }
+    /// The attributes used for popup windows such as the command history
+    pub fn popup_attributes(&self) -> u16 {
+        self.0.wPopupAttributes
+    }
+    /// The red, green and blue components of one of the 16 palette entries.
+    /// Fails with `ERROR_INVALID_PARAMETER` if `index` is not below 16.
+    pub fn color(&self, index: u8) -> Result<(u8, u8, u8)> {
+        let color = match self.0.ColorTable.get(index as usize) {
+            Some(&color) => color,
+            None => return Err(Error::new(ERROR_INVALID_PARAMETER)),
+        };
+        Ok((color as u8, (color >> 8) as u8, (color >> 16) as u8))
+    }
+    /// Changes one of the 16 palette entries.
+    /// This only modifies this structure; pass it to `ScreenBuffer::set_info_ex` to apply it.
+    /// # Panics
+    /// Panics if `index` is not below 16.
+    pub fn set_color(&mut self, index: u8, rgb: (u8, u8, u8)) {
+        assert!(index < 16, "color index {} out of range", index);
+        self.0.ColorTable[index as usize] =
+            rgb.0 as DWORD | (rgb.1 as DWORD) << 8 | (rgb.2 as DWORD) << 16;
+    }
+}
+/// The appearance of the cursor in a screen buffer
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CursorInfo {
+    /// The percentage of the character cell filled by the cursor, from 1 to 100
+    pub size: u32,
+    pub visible: bool,
 }
 #[repr(transparent)]
 #[derive(Copy, Clone)]
 pub struct FontInfoEx(CONSOLE_FONT_INFOEX);
+impl FontInfoEx {
+    /// The name of the typeface, such as `Consolas`
+    pub fn face_name(&self) -> OsString {
+        let name = &self.0.FaceName;
+        let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+        OsString::from_wide(&name[..len])
+    }
+    /// Sets the name of the typeface, truncating it to fit if necessary.
+    pub fn set_face_name<S: AsRef<OsStr>>(&mut self, name: S) {
+        let name = name.as_ref().to_wide();
+        let face = &mut self.0.FaceName;
+        // Always leave room for the nul terminator
+        let len = name.len().min(face.len() - 1);
+        face[..len].copy_from_slice(&name[..len]);
+        face[len..].iter_mut().for_each(|c| *c = 0);
+    }
+    /// The width and height of each character cell in logical units
+    pub fn size(&self) -> (i16, i16) {
+        (self.0.dwFontSize.X, self.0.dwFontSize.Y)
+    }
+    pub fn set_size(&mut self, size: (i16, i16)) {
+        self.0.dwFontSize = COORD {
+            X: size.0,
+            Y: size.1,
+        };
+    }
+    /// The index of the font in the system's console font table
+    pub fn index(&self) -> u32 {
+        self.0.nFont
+    }
+    /// The pitch and family, as in `TEXTMETRIC::tmPitchAndFamily`
+    pub fn family(&self) -> u32 {
+        self.0.FontFamily
+    }
+    pub fn set_family(&mut self, family: u32) {
+        self.0.FontFamily = family;
+    }
+    /// The weight, ranging from 100 to 1000 where 400 is normal and 700 is bold
+    pub fn weight(&self) -> u32 {
+        self.0.FontWeight
+    }
+    pub fn set_weight(&mut self, weight: u32) {
+        self.0.FontWeight = weight;
+    }
+}
 #[derive(Copy, Clone)]
 pub enum Input {
     Key {
@@ -280,6 +765,159 @@ pub enum Input {
     Menu(u32),
     Focus(bool),
 }
+/// A key press read by `InputBuffer::read_key`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyEvent {
+    pub repeat_count: u16,
+    /// The `VK_*` virtual key code
+    pub key_code: u16,
+    pub scan_code: u16,
+    /// The UTF-16 code unit the key produced, or 0 if it did not produce a character
+    pub wide_char: u16,
+    pub control_key_state: u32,
+}
+impl KeyEvent {
+    /// The character the key produced, if any.
+    /// Characters outside the Basic Multilingual Plane arrive as two key events each carrying
+    /// half of a surrogate pair, for which this returns `None`.
+    pub fn char(&self) -> Option<char> {
+        match self.wide_char {
+            0 => None,
+            c => char::from_u32(u32::from(c)),
+        }
+    }
+}
+impl Input {
+    // The button state and event flags, which are all clear for anything but a mouse event
+    fn mouse_state(&self) -> (u32, u32) {
+        match *self {
+            Input::Mouse {
+                button_state,
+                event_flags,
+                ..
+            } => (button_state, event_flags),
+            _ => (0, 0),
+        }
+    }
+    /// Whether this is a mouse event with the left button held down.
+    pub fn is_left_button(&self) -> bool {
+        self.mouse_state().0 & FROM_LEFT_1ST_BUTTON_PRESSED != 0
+    }
+    /// Whether this is a mouse event with the right button held down.
+    pub fn is_right_button(&self) -> bool {
+        self.mouse_state().0 & RIGHTMOST_BUTTON_PRESSED != 0
+    }
+    /// Whether this is a mouse event caused by the mouse moving.
+    pub fn is_moved(&self) -> bool {
+        self.mouse_state().1 & MOUSE_MOVED != 0
+    }
+    /// Whether this is the second click of a double click.
+    pub fn is_double_click(&self) -> bool {
+        self.mouse_state().1 & DOUBLE_CLICK != 0
+    }
+    /// Whether this is a mouse event caused by the vertical or horizontal wheel.
+    pub fn is_wheel(&self) -> bool {
+        self.mouse_state().1 & (MOUSE_WHEELED | MOUSE_HWHEELED) != 0
+    }
+    /// Whether this is a mouse event caused by the horizontal wheel.
+    pub fn is_horizontal_wheel(&self) -> bool {
+        self.mouse_state().1 & MOUSE_HWHEELED != 0
+    }
+    /// The distance the wheel was turned for wheel events, in multiples of `WHEEL_DELTA` (120).
+    /// Positive values are forward, away from the user, or to the right for the horizontal
+    /// wheel. The delta is the high word of `button_state`, which has to be reinterpreted as
+    /// signed rather than just shifted down.
+    pub fn wheel_delta(&self) -> Option<i16> {
+        if !self.is_wheel() {
+            return None;
+        }
+        Some((self.mouse_state().0 >> 16) as u16 as i16)
+    }
+    unsafe fn from_raw(input: &INPUT_RECORD) -> Input {
+        match input.EventType {
+            KEY_EVENT => {
+                let e = input.Event.KeyEvent();
+                Input::Key {
+                    key_down: e.bKeyDown != 0,
+                    repeat_count: e.wRepeatCount,
+                    key_code: e.wVirtualKeyCode,
+                    scan_code: e.wVirtualScanCode,
+                    wide_char: *e.uChar.UnicodeChar(),
+                    control_key_state: e.dwControlKeyState,
+                }
+            }
+            MOUSE_EVENT => {
+                let e = input.Event.MouseEvent();
+                Input::Mouse {
+                    position: (e.dwMousePosition.X, e.dwMousePosition.Y),
+                    button_state: e.dwButtonState,
+                    control_key_state: e.dwControlKeyState,
+                    event_flags: e.dwEventFlags,
+                }
+            }
+            WINDOW_BUFFER_SIZE_EVENT => {
+                let s = input.Event.WindowBufferSizeEvent().dwSize;
+                Input::WindowBufferSize(s.X, s.Y)
+            }
+            MENU_EVENT => Input::Menu(input.Event.MenuEvent().dwCommandId),
+            FOCUS_EVENT => Input::Focus(input.Event.FocusEvent().bSetFocus != 0),
+            e => unreachable!("invalid event type: {}", e),
+        }
+    }
+    fn to_raw(self) -> INPUT_RECORD {
+        let mut input: INPUT_RECORD = unsafe { zeroed() };
+        unsafe {
+            match self {
+                Input::Key {
+                    key_down,
+                    repeat_count,
+                    key_code,
+                    scan_code,
+                    wide_char,
+                    control_key_state,
+                } => {
+                    input.EventType = KEY_EVENT;
+                    let e = input.Event.KeyEvent_mut();
+                    e.bKeyDown = key_down as BOOL;
+                    e.wRepeatCount = repeat_count;
+                    e.wVirtualKeyCode = key_code;
+                    e.wVirtualScanCode = scan_code;
+                    *e.uChar.UnicodeChar_mut() = wide_char;
+                    e.dwControlKeyState = control_key_state;
+                }
+                Input::Mouse {
+                    position,
+                    button_state,
+                    control_key_state,
+                    event_flags,
+                } => {
+                    input.EventType = MOUSE_EVENT;
+                    let e = input.Event.MouseEvent_mut();
+                    e.dwMousePosition = COORD {
+                        X: position.0,
+                        Y: position.1,
+                    };
+                    e.dwButtonState = button_state;
+                    e.dwControlKeyState = control_key_state;
+                    e.dwEventFlags = event_flags;
+                }
+                Input::WindowBufferSize(x, y) => {
+                    input.EventType = WINDOW_BUFFER_SIZE_EVENT;
+                    input.Event.WindowBufferSizeEvent_mut().dwSize = COORD { X: x, Y: y };
+                }
+                Input::Menu(id) => {
+                    input.EventType = MENU_EVENT;
+                    input.Event.MenuEvent_mut().dwCommandId = id;
+                }
+                Input::Focus(focus) => {
+                    input.EventType = FOCUS_EVENT;
+                    input.Event.FocusEvent_mut().bSetFocus = focus as BOOL;
+                }
+            }
+        }
+        input
+    }
+}
 #[repr(transparent)]
 #[derive(Copy, Clone)]
 pub struct CharInfo(CHAR_INFO);
@@ -297,6 +935,103 @@ impl CharInfo {
         self.0.Attributes
     }
 }
+/// Whether standard output is a console, as opposed to being redirected to a file or pipe.
+/// This is the usual check for whether to emit colors and cursor movement.
+pub fn stdout_is_console() -> bool {
+    let handle = unsafe { GetStdHandle(STD_OUTPUT_HANDLE) };
+    // GetConsoleMode only succeeds for console handles
+    !handle.is_null() && handle != INVALID_HANDLE_VALUE && get_mode(handle).is_ok()
+}
+fn get_mode(handle: HANDLE) -> Result<ConsoleMode> {
+    let mut mode = 0;
+    let res = unsafe { GetConsoleMode(handle, &mut mode) };
+    if res == 0 {
+        return Error::last_result();
+    }
+    Ok(ConsoleMode(mode))
+}
+fn set_mode(handle: HANDLE, mode: ConsoleMode) -> Result<()> {
+    let res = unsafe { SetConsoleMode(handle, mode.0) };
+    if res == 0 {
+        return Error::last_result();
+    }
+    Ok(())
+}
+/// Input or output mode flags of a console buffer, which can be combined with `|`.
+/// Input and output flags share values, so only the ones matching the kind of buffer are
+/// meaningful.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ConsoleMode(pub u32);
+impl ConsoleMode {
+    pub const ENABLE_PROCESSED_INPUT: ConsoleMode = ConsoleMode(ENABLE_PROCESSED_INPUT);
+    pub const ENABLE_LINE_INPUT: ConsoleMode = ConsoleMode(ENABLE_LINE_INPUT);
+    pub const ENABLE_ECHO_INPUT: ConsoleMode = ConsoleMode(ENABLE_ECHO_INPUT);
+    pub const ENABLE_WINDOW_INPUT: ConsoleMode = ConsoleMode(ENABLE_WINDOW_INPUT);
+    pub const ENABLE_MOUSE_INPUT: ConsoleMode = ConsoleMode(ENABLE_MOUSE_INPUT);
+    pub const ENABLE_INSERT_MODE: ConsoleMode = ConsoleMode(ENABLE_INSERT_MODE);
+    pub const ENABLE_QUICK_EDIT_MODE: ConsoleMode = ConsoleMode(ENABLE_QUICK_EDIT_MODE);
+    pub const ENABLE_EXTENDED_FLAGS: ConsoleMode = ConsoleMode(ENABLE_EXTENDED_FLAGS);
+    pub const ENABLE_AUTO_POSITION: ConsoleMode = ConsoleMode(ENABLE_AUTO_POSITION);
+    pub const ENABLE_VIRTUAL_TERMINAL_INPUT: ConsoleMode =
+        ConsoleMode(ENABLE_VIRTUAL_TERMINAL_INPUT);
+    pub const ENABLE_PROCESSED_OUTPUT: ConsoleMode = ConsoleMode(ENABLE_PROCESSED_OUTPUT);
+    pub const ENABLE_WRAP_AT_EOL_OUTPUT: ConsoleMode = ConsoleMode(ENABLE_WRAP_AT_EOL_OUTPUT);
+    pub const ENABLE_VIRTUAL_TERMINAL_PROCESSING: ConsoleMode =
+        ConsoleMode(ENABLE_VIRTUAL_TERMINAL_PROCESSING);
+    pub const DISABLE_NEWLINE_AUTO_RETURN: ConsoleMode = ConsoleMode(DISABLE_NEWLINE_AUTO_RETURN);
+    pub const ENABLE_LVB_GRID_WORLDWIDE: ConsoleMode = ConsoleMode(ENABLE_LVB_GRID_WORLDWIDE);
+    /// Whether all of the flags in `other` are set
+    pub fn contains(self, other: ConsoleMode) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+impl BitOr for ConsoleMode {
+    type Output = ConsoleMode;
+    fn bitor(self, other: ConsoleMode) -> ConsoleMode {
+        ConsoleMode(self.0 | other.0)
+    }
+}
+impl BitAnd for ConsoleMode {
+    type Output = ConsoleMode;
+    fn bitand(self, other: ConsoleMode) -> ConsoleMode {
+        ConsoleMode(self.0 & other.0)
+    }
+}
+impl Not for ConsoleMode {
+    type Output = ConsoleMode;
+    fn not(self) -> ConsoleMode {
+        ConsoleMode(!self.0)
+    }
+}
+impl From<ConsoleMode> for u32 {
+    fn from(mode: ConsoleMode) -> u32 {
+        mode.0
+    }
+}
+/// Character attributes for the foreground and background colors, which can be combined with `|`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Color(pub u16);
+impl Color {
+    pub const FOREGROUND_BLUE: Color = Color(FOREGROUND_BLUE);
+    pub const FOREGROUND_GREEN: Color = Color(FOREGROUND_GREEN);
+    pub const FOREGROUND_RED: Color = Color(FOREGROUND_RED);
+    pub const FOREGROUND_INTENSITY: Color = Color(FOREGROUND_INTENSITY);
+    pub const BACKGROUND_BLUE: Color = Color(BACKGROUND_BLUE);
+    pub const BACKGROUND_GREEN: Color = Color(BACKGROUND_GREEN);
+    pub const BACKGROUND_RED: Color = Color(BACKGROUND_RED);
+    pub const BACKGROUND_INTENSITY: Color = Color(BACKGROUND_INTENSITY);
+}
+impl BitOr for Color {
+    type Output = Color;
+    fn bitor(self, other: Color) -> Color {
+        Color(self.0 | other.0)
+    }
+}
+impl From<Color> for u16 {
+    fn from(color: Color) -> u16 {
+        color.0
+    }
+}
 /// Allocates a console if the process does not already have a console.
 pub fn alloc() -> Result<()> {
     match unsafe { AllocConsole() } {
@@ -343,3 +1078,136 @@ pub fn set_output_code_page(code: u32) -> Result<()> {
     }
     Ok(())
 }
+/// Gets the title of the current console window
+pub fn title() -> Result<OsString> {
+    let mut buf = vec![0u16; 256];
+    loop {
+        unsafe { SetLastError(0) };
+        let len = unsafe { GetConsoleTitleW(buf.as_mut_ptr(), buf.len() as DWORD) } as usize;
+        if len == 0 {
+            let err = Error::last();
+            if err.code() != 0 {
+                return Err(err);
+            }
+        }
+        // The title may have been truncated if it filled the entire buffer
+        if len + 1 < buf.len() {
+            return Ok(OsString::from_wide(&buf[..len]));
+        }
+        let new_len = buf.len() * 2;
+        buf.resize(new_len, 0);
+    }
+}
+/// Sets the title of the current console window
+pub fn set_title<S: AsRef<OsStr>>(title: S) -> Result<()> {
+    let res = unsafe { SetConsoleTitleW(title.to_wide_null().as_ptr()) };
+    if res == 0 {
+        return Error::last_result();
+    }
+    Ok(())
+}
+/// A console control signal delivered to the process
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CtrlEvent {
+    CtrlC,
+    CtrlBreak,
+    /// The console window is being closed
+    Close,
+    /// The user is logging off; only delivered to services
+    Logoff,
+    /// The system is shutting down; only delivered to services
+    Shutdown,
+}
+impl CtrlEvent {
+    fn from_raw(ctrl_type: DWORD) -> Option<CtrlEvent> {
+        match ctrl_type {
+            CTRL_C_EVENT => Some(CtrlEvent::CtrlC),
+            CTRL_BREAK_EVENT => Some(CtrlEvent::CtrlBreak),
+            CTRL_CLOSE_EVENT => Some(CtrlEvent::Close),
+            CTRL_LOGOFF_EVENT => Some(CtrlEvent::Logoff),
+            CTRL_SHUTDOWN_EVENT => Some(CtrlEvent::Shutdown),
+            _ => None,
+        }
+    }
+}
+type CtrlHandler = Arc<dyn Fn(CtrlEvent) -> bool + Send + Sync>;
+// A const Mutex::new needs Rust 1.63, the minimum version declared in Cargo.toml
+static CTRL_HANDLERS: Mutex<Vec<(usize, CtrlHandler)>> = Mutex::new(Vec::new());
+static NEXT_CTRL_HANDLER: AtomicUsize = AtomicUsize::new(0);
+fn ctrl_handlers() -> MutexGuard<'static, Vec<(usize, CtrlHandler)>> {
+    // A panicking handler must not take the rest of them down with it
+    CTRL_HANDLERS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+unsafe extern "system" fn ctrl_trampoline(ctrl_type: DWORD) -> BOOL {
+    let event = match CtrlEvent::from_raw(ctrl_type) {
+        Some(event) => event,
+        None => return FALSE,
+    };
+    // Clone the handlers out so they are free to register or unregister handlers themselves
+    let handlers: Vec<CtrlHandler> = ctrl_handlers().iter().map(|h| h.1.clone()).collect();
+    // Like the system, call the most recently registered handler first. Unwinding out of this
+    // callback is not allowed, so a handler which panics is treated as not handling the event.
+    handlers
+        .iter()
+        .rev()
+        .any(|handler| catch_unwind(AssertUnwindSafe(|| handler(event))).unwrap_or(false))
+        as BOOL
+}
+/// Registers a handler for console control signals such as Ctrl+C.
+/// Handlers run on a thread created by the system, most recently registered first, until one
+/// returns `true` to mark the event as handled. If none do, the default handling applies, which
+/// usually terminates the process.
+/// The handler stays registered until the returned guard is dropped.
+pub fn set_ctrl_handler<F>(handler: F) -> Result<CtrlHandlerGuard>
+where
+    F: Fn(CtrlEvent) -> bool + Send + Sync + 'static,
+{
+    let mut handlers = ctrl_handlers();
+    if handlers.is_empty() {
+        let res = unsafe { SetConsoleCtrlHandler(Some(ctrl_trampoline), TRUE) };
+        if res == 0 {
+            return Error::last_result();
+        }
+    }
+    let id = NEXT_CTRL_HANDLER.fetch_add(1, Ordering::Relaxed);
+    handlers.push((id, Arc::new(handler)));
+    Ok(CtrlHandlerGuard(id))
+}
+/// Unregisters its console control handler when dropped
+#[derive(Debug)]
+pub struct CtrlHandlerGuard(usize);
+impl Drop for CtrlHandlerGuard {
+    fn drop(&mut self) {
+        let mut handlers = ctrl_handlers();
+        handlers.retain(|h| h.0 != self.0);
+        if handlers.is_empty() {
+            unsafe { SetConsoleCtrlHandler(Some(ctrl_trampoline), FALSE) };
+        }
+    }
+}
+/// Sends Ctrl+C or Ctrl+Break to a process group sharing this console.
+/// A `process_group_id` of 0 sends it to every process attached to the console, including this
+/// one. Only `CtrlC` and `CtrlBreak` can be generated; other events fail with
+/// `ERROR_INVALID_PARAMETER`.
+pub fn generate_ctrl_event(event: CtrlEvent, process_group_id: u32) -> Result<()> {
+    let ctrl_type = match event {
+        CtrlEvent::CtrlC => CTRL_C_EVENT,
+        CtrlEvent::CtrlBreak => CTRL_BREAK_EVENT,
+        _ => return Err(Error::new(ERROR_INVALID_PARAMETER)),
+    };
+    let res = unsafe { GenerateConsoleCtrlEvent(ctrl_type, process_group_id) };
+    if res == 0 {
+        return Error::last_result();
+    }
+    Ok(())
+}
+/// The window used by the console of the calling process, or `None` if there isn't one.
+/// The handle is only valid for as long as the console exists.
+pub fn window() -> Option<HWND> {
+    let hwnd = unsafe { GetConsoleWindow() };
+    if hwnd.is_null() {
+        None
+    } else {
+        Some(hwnd)
+    }
+}
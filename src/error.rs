@@ -3,21 +3,131 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // All files in the project carrying such notice may not be copied, modified, or distributed
 // except according to those terms.
-use std::result;
-use winapi::shared::minwindef::DWORD;
+use std::{
+    error,
+    fmt::{Display, Error as FmtError, Formatter},
+    hash::{Hash, Hasher},
+    io, result,
+};
+use winapi::shared::minwindef::{BOOL, DWORD};
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror::{
+    FACILITY_WIN32, HRESULT_CODE, HRESULT_FACILITY, HRESULT_FROM_WIN32,
+};
 use winapi::um::errhandlingapi::GetLastError;
+use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+use winapi::um::winnt::HANDLE;
+/// A Win32 error code or a failed HRESULT.
+/// Errors compare equal when they have the same `to_hresult`, so a Win32 error equals the
+/// HRESULT wrapping it, such as `ERROR_ACCESS_DENIED` and `E_ACCESSDENIED`.
 #[derive(Clone, Copy, Debug)]
-pub struct Error(DWORD);
+pub struct Error {
+    code: DWORD,
+    hresult: bool,
+}
 impl Error {
+    /// For errors created with `from_hresult` this is the HRESULT reinterpreted as a `u32`.
     pub fn code(&self) -> u32 {
-        self.0
+        self.code
+    }
+    /// Recovers the error from an `io::Error` which carries a raw OS error code.
+    pub fn from_io(err: &io::Error) -> Option<Error> {
+        err.raw_os_error().map(|code| Error::new(code as DWORD))
+    }
+    /// Creates an error from a failed HRESULT returned by a COM method.
+    pub fn from_hresult(hr: HRESULT) -> Error {
+        Error {
+            code: hr as DWORD,
+            hresult: true,
+        }
+    }
+    /// Whether this error was created from an HRESULT rather than a Win32 error code.
+    pub fn is_hresult(&self) -> bool {
+        self.hresult
+    }
+    /// Returns the HRESULT this error was created from, or converts the Win32 error code into
+    /// one using `HRESULT_FROM_WIN32`.
+    pub fn to_hresult(&self) -> HRESULT {
+        if self.hresult {
+            self.code as HRESULT
+        } else {
+            HRESULT_FROM_WIN32(self.code)
+        }
+    }
+    pub(crate) fn new(code: DWORD) -> Error {
+        Error {
+            code,
+            hresult: false,
+        }
     }
     pub(crate) fn last() -> Error {
-        Error(unsafe { GetLastError() })
+        Error::new(unsafe { GetLastError() })
     }
     pub(crate) fn last_result<T>() -> Result<T> {
         Err(Error::last())
     }
 }
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> result::Result<(), FmtError> {
+        if !self.hresult {
+            return write!(f, "Win32 error {}", self.code);
+        }
+        let hr = self.code as HRESULT;
+        if HRESULT_FACILITY(hr) == FACILITY_WIN32 {
+            write!(
+                f,
+                "HRESULT 0x{:08X} (Win32 error {})",
+                self.code,
+                HRESULT_CODE(hr)
+            )
+        } else {
+            write!(f, "HRESULT 0x{:08X}", self.code)
+        }
+    }
+}
+impl PartialEq for Error {
+    fn eq(&self, other: &Error) -> bool {
+        self.to_hresult() == other.to_hresult()
+    }
+}
+impl Eq for Error {}
+impl Hash for Error {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.to_hresult().hash(state)
+    }
+}
+impl error::Error for Error {}
+impl From<Error> for io::Error {
+    fn from(err: Error) -> io::Error {
+        io::Error::from_raw_os_error(err.code as i32)
+    }
+}
 
 pub type Result<T> = result::Result<T, Error>;
+
+/// Checks the `BOOL` returned by a raw FFI call, turning failure into the last error.
+pub trait BoolResultExt {
+    fn ok_or_last_error(self) -> Result<()>;
+}
+impl BoolResultExt for BOOL {
+    fn ok_or_last_error(self) -> Result<()> {
+        match self {
+            0 => Error::last_result(),
+            _ => Ok(()),
+        }
+    }
+}
+/// Checks the `HANDLE` returned by a raw FFI call, treating both null and
+/// `INVALID_HANDLE_VALUE` as failure and turning it into the last error.
+pub trait HandleResultExt {
+    fn ok_or_last_error(self) -> Result<HANDLE>;
+}
+impl HandleResultExt for HANDLE {
+    fn ok_or_last_error(self) -> Result<HANDLE> {
+        if self.is_null() || self == INVALID_HANDLE_VALUE {
+            Error::last_result()
+        } else {
+            Ok(self)
+        }
+    }
+}
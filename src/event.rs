@@ -0,0 +1,105 @@
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// All files in the project carrying such notice may not be copied, modified, or distributed
+// except according to those terms.
+use error::{Error, Result};
+use handle::Handle;
+use security::SecurityAttributes;
+use std::{os::windows::io::AsRawHandle, time::Duration};
+use wait::timeout_ms;
+use wide::ToWide;
+use winapi::{
+    shared::{
+        minwindef::{BOOL, FALSE},
+        winerror::WAIT_TIMEOUT,
+    },
+    um::{
+        synchapi::{CreateEventW, OpenEventW, ResetEvent, SetEvent, WaitForSingleObject},
+        winbase::{INFINITE, WAIT_OBJECT_0},
+        winnt::{EVENT_MODIFY_STATE, HANDLE, SYNCHRONIZE},
+    },
+};
+
+/// The outcome of a wait which did not fail
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WaitState {
+    Signaled,
+    TimedOut,
+}
+
+/// A named event object
+#[derive(Debug)]
+pub struct Event(Handle);
+impl Event {
+    /// A manual reset event stays signaled until it is reset, releasing every waiting thread.
+    /// An auto reset event is reset as soon as a single waiting thread is released.
+    pub fn create(
+        manual_reset: bool,
+        initial_state: bool,
+        security_attributes: Option<&SecurityAttributes>,
+        name: &str,
+    ) -> Result<Event> {
+        let mut security_attributes = SecurityAttributes::as_raw_opt(security_attributes);
+        unsafe {
+            let handle = CreateEventW(
+                SecurityAttributes::raw_opt_ptr(&mut security_attributes),
+                manual_reset as BOOL,
+                initial_state as BOOL,
+                name.to_wide_null().as_ptr(),
+            );
+            if handle.is_null() {
+                return Error::last_result();
+            }
+            Ok(Event(Handle::new(handle)))
+        }
+    }
+    pub fn open(name: &str) -> Result<Event> {
+        unsafe {
+            let handle = OpenEventW(
+                EVENT_MODIFY_STATE | SYNCHRONIZE,
+                FALSE,
+                name.to_wide_null().as_ptr(),
+            );
+            if handle.is_null() {
+                return Error::last_result();
+            }
+            Ok(Event(Handle::new(handle)))
+        }
+    }
+    pub fn set(&self) -> Result<()> {
+        match unsafe { SetEvent(*self.0) } {
+            0 => Error::last_result(),
+            _ => Ok(()),
+        }
+    }
+    pub fn reset(&self) -> Result<()> {
+        match unsafe { ResetEvent(*self.0) } {
+            0 => Error::last_result(),
+            _ => Ok(()),
+        }
+    }
+    /// The timeout is specified in milliseconds
+    /// Specifying None for the timeout means to wait forever
+    pub fn wait(&self, timeout: Option<u32>) -> Result<WaitState> {
+        match unsafe { WaitForSingleObject(*self.0, timeout.unwrap_or(INFINITE)) } {
+            WAIT_OBJECT_0 => Ok(WaitState::Signaled),
+            WAIT_TIMEOUT => Ok(WaitState::TimedOut),
+            _ => Error::last_result(),
+        }
+    }
+    /// Like `wait`, but with the timeout as a `Duration` which is rounded up to whole milliseconds
+    pub fn wait_timeout(&self, timeout: Option<Duration>) -> Result<WaitState> {
+        self.wait(Some(timeout_ms(timeout)))
+    }
+    pub fn try_clone(&self) -> Result<Event> {
+        unsafe { Handle::duplicate_from(*self.0).map(Event) }
+    }
+}
+unsafe impl Send for Event {}
+unsafe impl Sync for Event {}
+impl AsRawHandle for Event {
+    fn as_raw_handle(&self) -> HANDLE {
+        *self.0
+    }
+}
@@ -0,0 +1,156 @@
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// All files in the project carrying such notice may not be copied, modified, or distributed
+// except according to those terms.
+use error::{Error, Result};
+use handle::Handle;
+use security::SecurityAttributes;
+use std::{
+    ffi::OsStr,
+    os::windows::io::{AsRawHandle, FromRawHandle, IntoRawHandle},
+    ptr::null_mut,
+};
+use wide::ToWide;
+use winapi::{
+    shared::minwindef::DWORD,
+    um::{
+        fileapi::{
+            CreateFileW, CREATE_ALWAYS, CREATE_NEW, OPEN_ALWAYS, OPEN_EXISTING, TRUNCATE_EXISTING,
+        },
+        handleapi::INVALID_HANDLE_VALUE,
+        winbase::FILE_FLAG_OVERLAPPED,
+        winnt::{FILE_SHARE_READ, GENERIC_READ, GENERIC_WRITE, HANDLE},
+    },
+};
+
+/// A file opened for overlapped I/O.
+pub struct File(Handle);
+impl File {
+    /// Opens the file for reading and writing with overlapped I/O, the same as
+    /// `OpenOptions::new().mode(mode).open(path)`.
+    pub fn open<P: AsRef<OsStr>>(path: P, mode: OpenMode) -> Result<File> {
+        OpenOptions::new().mode(mode).open(path)
+    }
+    /// The underlying handle, for registering with a `queue::Queue`.
+    pub fn handle(&self) -> &Handle {
+        &self.0
+    }
+    pub fn into_handle(self) -> Handle {
+        self.0
+    }
+}
+impl AsRawHandle for File {
+    fn as_raw_handle(&self) -> HANDLE {
+        self.0.as_raw_handle()
+    }
+}
+impl IntoRawHandle for File {
+    fn into_raw_handle(self) -> HANDLE {
+        self.0.into_raw_handle()
+    }
+}
+impl FromRawHandle for File {
+    unsafe fn from_raw_handle(handle: HANDLE) -> File {
+        File(Handle::from_raw_handle(handle))
+    }
+}
+
+/// What to do depending on whether the file already exists
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OpenMode {
+    /// Fails if the file exists
+    CreateNew,
+    /// Truncates the file if it exists
+    CreateAlways,
+    /// Fails if the file does not exist
+    OpenExisting,
+    /// Creates the file if it does not exist
+    OpenAlways,
+    /// Truncates the file, failing if it does not exist
+    TruncateExisting,
+}
+impl OpenMode {
+    fn to_raw(self) -> DWORD {
+        match self {
+            OpenMode::CreateNew => CREATE_NEW,
+            OpenMode::CreateAlways => CREATE_ALWAYS,
+            OpenMode::OpenExisting => OPEN_EXISTING,
+            OpenMode::OpenAlways => OPEN_ALWAYS,
+            OpenMode::TruncateExisting => TRUNCATE_EXISTING,
+        }
+    }
+}
+
+/// Options for `CreateFileW`.
+/// Defaults to read and write access, shared reads, `OpenMode::OpenExisting` and
+/// `FILE_FLAG_OVERLAPPED`.
+#[derive(Clone, Copy)]
+pub struct OpenOptions<'a> {
+    access: DWORD,
+    share_mode: DWORD,
+    flags_and_attributes: DWORD,
+    mode: OpenMode,
+    security_attributes: Option<&'a SecurityAttributes<'a>>,
+}
+impl<'a> OpenOptions<'a> {
+    pub fn new() -> OpenOptions<'a> {
+        OpenOptions {
+            access: GENERIC_READ | GENERIC_WRITE,
+            share_mode: FILE_SHARE_READ,
+            flags_and_attributes: FILE_FLAG_OVERLAPPED,
+            mode: OpenMode::OpenExisting,
+            security_attributes: None,
+        }
+    }
+    /// The `GENERIC_*` or specific access rights to request
+    pub fn access(&mut self, access: u32) -> &mut OpenOptions<'a> {
+        self.access = access;
+        self
+    }
+    /// Which `FILE_SHARE_*` access other openers of the file are allowed
+    pub fn share_mode(&mut self, share_mode: u32) -> &mut OpenOptions<'a> {
+        self.share_mode = share_mode;
+        self
+    }
+    /// The combined `FILE_FLAG_*` and `FILE_ATTRIBUTE_*` values. This replaces the default, so
+    /// include `FILE_FLAG_OVERLAPPED` to keep using the handle with a completion port.
+    pub fn flags_and_attributes(&mut self, flags_and_attributes: u32) -> &mut OpenOptions<'a> {
+        self.flags_and_attributes = flags_and_attributes;
+        self
+    }
+    pub fn mode(&mut self, mode: OpenMode) -> &mut OpenOptions<'a> {
+        self.mode = mode;
+        self
+    }
+    pub fn security_attributes(
+        &mut self,
+        security_attributes: &'a SecurityAttributes<'a>,
+    ) -> &mut OpenOptions<'a> {
+        self.security_attributes = Some(security_attributes);
+        self
+    }
+    pub fn open<P: AsRef<OsStr>>(&self, path: P) -> Result<File> {
+        let mut security_attributes = SecurityAttributes::as_raw_opt(self.security_attributes);
+        let handle = unsafe {
+            CreateFileW(
+                path.as_ref().to_wide_null().as_ptr(),
+                self.access,
+                self.share_mode,
+                SecurityAttributes::raw_opt_ptr(&mut security_attributes),
+                self.mode.to_raw(),
+                self.flags_and_attributes,
+                null_mut(),
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Error::last_result();
+        }
+        Ok(File(unsafe { Handle::new(handle) }))
+    }
+}
+impl<'a> Default for OpenOptions<'a> {
+    fn default() -> OpenOptions<'a> {
+        OpenOptions::new()
+    }
+}
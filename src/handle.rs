@@ -5,16 +5,33 @@
 // except according to those terms.
 use error::{Error, Result};
 use std::{
+    fmt::{Debug, Formatter, Result as FmtResult},
     ops::Deref,
-    os::windows::io::{AsRawHandle, FromRawHandle, IntoRawHandle},
+    os::windows::io::{
+        AsHandle, AsRawHandle, BorrowedHandle, FromRawHandle, IntoRawHandle, OwnedHandle,
+    },
     ptr::null_mut,
+    time::Duration,
     mem::ManuallyDrop,
 };
+use wait::timeout_ms;
 use winapi::{
-    shared::minwindef::FALSE,
+    shared::{
+        minwindef::{BOOL, DWORD},
+        winerror::{ERROR_ABANDONED_WAIT_0, WAIT_TIMEOUT},
+    },
     um::{
-        handleapi::{CloseHandle, DuplicateHandle},
+        fileapi::GetFileType,
+        handleapi::{
+            CloseHandle, DuplicateHandle, GetHandleInformation, SetHandleInformation,
+            INVALID_HANDLE_VALUE,
+        },
         processthreadsapi::GetCurrentProcess,
+        synchapi::WaitForSingleObject,
+        winbase::{
+            FILE_TYPE_CHAR, FILE_TYPE_DISK, FILE_TYPE_PIPE, HANDLE_FLAG_INHERIT, INFINITE,
+            WAIT_ABANDONED, WAIT_OBJECT_0,
+        },
         winnt::{DUPLICATE_SAME_ACCESS, HANDLE},
     },
 };
@@ -25,6 +42,19 @@ impl Handle {
     pub unsafe fn new(handle: HANDLE) -> Handle {
         Handle(handle)
     }
+    /// A null handle, for use as a placeholder until a real handle is available.
+    /// Dropping it does nothing.
+    pub fn null() -> Handle {
+        Handle(null_mut())
+    }
+    pub fn is_null(&self) -> bool {
+        self.0.is_null()
+    }
+    /// Whether this is `INVALID_HANDLE_VALUE`, which some functions return on failure.
+    /// Note this is also the value of the pseudo handle for the current process.
+    pub fn is_invalid(&self) -> bool {
+        self.0 == INVALID_HANDLE_VALUE
+    }
     pub fn close(self) -> Result<()> {
         match unsafe { CloseHandle(self.into_raw_handle()) } {
             0 => Error::last_result(),
@@ -33,27 +63,93 @@ impl Handle {
     }
     // Duplicates the handle without taking ownership
     pub unsafe fn duplicate_from(handle: HANDLE) -> Result<Handle> {
-        let mut new_handle = null_mut();
-        let res = DuplicateHandle(
-            GetCurrentProcess(),
-            handle,
-            GetCurrentProcess(),
-            &mut new_handle,
-            0,
-            FALSE,
-            DUPLICATE_SAME_ACCESS,
-        );
-        match res {
+        duplicate(handle, 0, false, DUPLICATE_SAME_ACCESS)
+    }
+    pub fn try_clone(&self) -> Result<Handle> {
+        unsafe { duplicate(self.0, 0, false, DUPLICATE_SAME_ACCESS) }
+    }
+    /// Duplicates the handle with different access rights, which can be more restricted than
+    /// those of the original, and optionally allows it to be inherited by child processes.
+    pub fn try_clone_with_access(&self, access: DWORD, inheritable: bool) -> Result<Handle> {
+        unsafe { duplicate(self.0, access, inheritable, 0) }
+    }
+    /// Whether child processes will inherit this handle
+    pub fn is_inheritable(&self) -> Result<bool> {
+        let mut flags = 0;
+        match unsafe { GetHandleInformation(self.0, &mut flags) } {
+            0 => Error::last_result(),
+            _ => Ok(flags & HANDLE_FLAG_INHERIT != 0),
+        }
+    }
+    /// Leaves the other handle flags, such as `HANDLE_FLAG_PROTECT_FROM_CLOSE`, untouched.
+    pub fn set_inheritable(&self, inheritable: bool) -> Result<()> {
+        let flags = if inheritable { HANDLE_FLAG_INHERIT } else { 0 };
+        match unsafe { SetHandleInformation(self.0, HANDLE_FLAG_INHERIT, flags) } {
             0 => Error::last_result(),
-            _ => Ok(Handle(new_handle)),
+            _ => Ok(()),
+        }
+    }
+    /// Waits forever for the object to be signaled
+    pub fn wait(&self) -> Result<()> {
+        self.wait_ms(INFINITE).map(|_| ())
+    }
+    /// Waits for the object to be signaled, returning `false` if the timeout elapsed first.
+    /// The timeout is rounded up to whole milliseconds.
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<bool> {
+        self.wait_ms(timeout_ms(Some(timeout)))
+    }
+    // An abandoned mutex is reported as ERROR_ABANDONED_WAIT_0 since there is no guard to hand out
+    pub(crate) fn wait_ms(&self, timeout: DWORD) -> Result<bool> {
+        match unsafe { WaitForSingleObject(self.0, timeout) } {
+            WAIT_OBJECT_0 => Ok(true),
+            WAIT_TIMEOUT => Ok(false),
+            WAIT_ABANDONED => Err(Error::new(ERROR_ABANDONED_WAIT_0)),
+            _ => Error::last_result(),
         }
     }
 }
+unsafe fn duplicate(
+    handle: HANDLE,
+    access: DWORD,
+    inheritable: bool,
+    options: DWORD,
+) -> Result<Handle> {
+    let mut new_handle = null_mut();
+    let res = DuplicateHandle(
+        GetCurrentProcess(),
+        handle,
+        GetCurrentProcess(),
+        &mut new_handle,
+        access,
+        inheritable as BOOL,
+        options,
+    );
+    match res {
+        0 => Error::last_result(),
+        _ => Ok(Handle(new_handle)),
+    }
+}
 impl AsRawHandle for Handle {
     fn as_raw_handle(&self) -> HANDLE {
         self.0
     }
 }
+impl Debug for Handle {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let mut s = f.debug_struct("Handle");
+        s.field("raw", &self.0);
+        let kind = match unsafe { GetFileType(self.0) } {
+            FILE_TYPE_CHAR => Some("Char"),
+            FILE_TYPE_DISK => Some("Disk"),
+            FILE_TYPE_PIPE => Some("Pipe"),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            s.field("kind", &kind);
+        }
+        s.finish()
+    }
+}
 impl Deref for Handle {
     type Target = HANDLE;
     fn deref(&self) -> &HANDLE {
@@ -62,6 +158,10 @@ impl Deref for Handle {
 }
 impl Drop for Handle {
     fn drop(&mut self) {
+        // Sentinels don't refer to anything that needs closing
+        if self.is_null() || self.is_invalid() {
+            return;
+        }
         let ret = unsafe { CloseHandle(self.0) };
         let err = Error::last();
         assert!(ret != 0, "{:?}", err);
@@ -77,3 +177,18 @@ impl IntoRawHandle for Handle {
         ManuallyDrop::new(self).0
     }
 }
+impl AsHandle for Handle {
+    fn as_handle(&self) -> BorrowedHandle<'_> {
+        unsafe { BorrowedHandle::borrow_raw(self.0) }
+    }
+}
+impl From<OwnedHandle> for Handle {
+    fn from(handle: OwnedHandle) -> Handle {
+        Handle(handle.into_raw_handle())
+    }
+}
+impl From<Handle> for OwnedHandle {
+    fn from(handle: Handle) -> OwnedHandle {
+        unsafe { OwnedHandle::from_raw_handle(handle.into_raw_handle()) }
+    }
+}
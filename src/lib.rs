@@ -7,18 +7,29 @@
 #![allow(clippy::missing_safety_doc, clippy::len_without_is_empty)]
 extern crate winapi;
 
-// pub mod apc;
+pub mod apc;
 pub mod bstr;
 pub mod com;
 pub mod console;
 pub mod error;
+pub mod event;
+pub mod file;
 pub mod handle;
+pub mod mmap;
 pub mod mutex;
-// pub mod perf;
-// pub mod pipe;
-// pub mod sleep;
-// pub mod thread;
+pub mod perf;
+pub mod pipe;
+pub mod process;
+pub mod queue;
+pub mod reg;
+pub mod reparse;
+pub mod security;
+pub mod semaphore;
+pub mod sleep;
+pub mod thread;
+pub mod timer;
 pub mod vsb;
+pub mod wait;
 pub mod wide;
 
-pub use error::{Error, Result};
+pub use error::{BoolResultExt, Error, HandleResultExt, Result};
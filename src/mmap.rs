@@ -0,0 +1,154 @@
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// All files in the project carrying such notice may not be copied, modified, or distributed
+// except according to those terms.
+use error::{Error, Result};
+use handle::Handle;
+use std::{
+    mem::zeroed,
+    ops::{Deref, DerefMut},
+    os::windows::io::AsRawHandle,
+    ptr::null_mut,
+    slice::{from_raw_parts, from_raw_parts_mut},
+    sync::atomic::{AtomicU32, Ordering::Relaxed},
+};
+use winapi::{
+    shared::{
+        minwindef::DWORD,
+        winerror::{ERROR_INVALID_PARAMETER, ERROR_MAPPED_ALIGNMENT},
+    },
+    um::{
+        memoryapi::{
+            CreateFileMappingW, FlushViewOfFile, MapViewOfFile, UnmapViewOfFile, FILE_MAP_COPY,
+            FILE_MAP_WRITE,
+        },
+        sysinfoapi::{GetSystemInfo, SYSTEM_INFO},
+        winnt::HANDLE,
+    },
+};
+
+/// The granularity that view offsets must be aligned to.
+/// This is fixed for the system, so it is only queried once.
+pub fn allocation_granularity() -> u32 {
+    // The granularity is never 0, so that marks it as not queried yet
+    static GRANULARITY: AtomicU32 = AtomicU32::new(0);
+    let granularity = GRANULARITY.load(Relaxed);
+    if granularity != 0 {
+        return granularity;
+    }
+    let granularity = unsafe {
+        let mut info: SYSTEM_INFO = zeroed();
+        GetSystemInfo(&mut info);
+        info.dwAllocationGranularity
+    };
+    GRANULARITY.store(granularity, Relaxed);
+    granularity
+}
+
+/// A file mapping object, from which views of the file can be mapped into memory
+#[derive(Debug)]
+pub struct FileMapping(Handle);
+impl FileMapping {
+    /// Creates an unnamed mapping of the file with the given `PAGE_*` protection.
+    /// A `max_size` of 0 maps the whole file at its current size.
+    pub fn create<H: AsRawHandle>(
+        handle: &H,
+        protect: DWORD,
+        max_size: u64,
+    ) -> Result<FileMapping> {
+        let mapping = unsafe {
+            CreateFileMappingW(
+                handle.as_raw_handle(),
+                null_mut(),
+                protect,
+                (max_size >> 32) as DWORD,
+                max_size as DWORD,
+                null_mut(),
+            )
+        };
+        if mapping.is_null() {
+            return Error::last_result();
+        }
+        Ok(FileMapping(unsafe { Handle::new(mapping) }))
+    }
+    /// Maps `len` bytes starting at `offset` with the given `FILE_MAP_*` access.
+    /// `offset` must be a multiple of `allocation_granularity`, otherwise this fails with
+    /// `ERROR_MAPPED_ALIGNMENT`, and `len` must not be 0.
+    pub fn map(&self, access: DWORD, offset: u64, len: usize) -> Result<MapView> {
+        // The granularity is always a power of two
+        if offset & (u64::from(allocation_granularity()) - 1) != 0 {
+            return Err(Error::new(ERROR_MAPPED_ALIGNMENT));
+        }
+        if len == 0 {
+            return Err(Error::new(ERROR_INVALID_PARAMETER));
+        }
+        let ptr = unsafe {
+            MapViewOfFile(
+                *self.0,
+                access,
+                (offset >> 32) as DWORD,
+                offset as DWORD,
+                len,
+            )
+        };
+        if ptr.is_null() {
+            return Error::last_result();
+        }
+        Ok(MapView {
+            ptr: ptr.cast(),
+            len,
+            writable: access & (FILE_MAP_WRITE | FILE_MAP_COPY) != 0,
+        })
+    }
+}
+impl AsRawHandle for FileMapping {
+    fn as_raw_handle(&self) -> HANDLE {
+        self.0.as_raw_handle()
+    }
+}
+
+/// A view of a file mapping which is unmapped on drop.
+/// The view keeps the mapping alive on its own, so it may outlive the `FileMapping`.
+#[derive(Debug)]
+pub struct MapView {
+    ptr: *mut u8,
+    len: usize,
+    writable: bool,
+}
+impl MapView {
+    /// Writes the modified pages of the view back to the file.
+    pub fn flush(&self) -> Result<()> {
+        match unsafe { FlushViewOfFile(self.ptr.cast(), self.len) } {
+            0 => Error::last_result(),
+            _ => Ok(()),
+        }
+    }
+    pub fn is_writable(&self) -> bool {
+        self.writable
+    }
+}
+impl Deref for MapView {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        unsafe { from_raw_parts(self.ptr, self.len) }
+    }
+}
+impl DerefMut for MapView {
+    /// # Panics
+    /// Panics if the view was not mapped with `FILE_MAP_WRITE` or `FILE_MAP_COPY`, since writing
+    /// to it would fault.
+    fn deref_mut(&mut self) -> &mut [u8] {
+        assert!(self.writable, "the view was not mapped for writing");
+        unsafe { from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+impl Drop for MapView {
+    fn drop(&mut self) {
+        unsafe {
+            UnmapViewOfFile(self.ptr.cast());
+        }
+    }
+}
+unsafe impl Send for MapView {}
+unsafe impl Sync for MapView {}
@@ -6,49 +6,37 @@
 
 use error::Error;
 use handle::Handle;
+pub use security::SecurityAttributes;
 use std::{
     fmt::{Debug, Error as FmtError, Formatter},
     marker::PhantomData,
-    mem::size_of,
     ops::Deref,
-    ptr::null_mut,
+    os::windows::io::AsRawHandle,
+    time::Duration,
 };
+use wait::timeout_ms;
 use wide::ToWide;
 use winapi::{
     shared::{minwindef::FALSE, winerror::WAIT_TIMEOUT},
     um::{
         errhandlingapi::GetLastError,
-        minwinbase::SECURITY_ATTRIBUTES,
         synchapi::{CreateMutexW, OpenMutexW, ReleaseMutex, WaitForSingleObject},
         winbase::{INFINITE, WAIT_ABANDONED, WAIT_OBJECT_0},
-        winnt::{HANDLE, SECURITY_DESCRIPTOR, SYNCHRONIZE},
+        winnt::{HANDLE, SYNCHRONIZE},
     },
 };
 
-pub struct SecurityAttributes(SECURITY_ATTRIBUTES);
-impl SecurityAttributes {
-    pub unsafe fn from_raw(sd: *mut SECURITY_DESCRIPTOR) -> SecurityAttributes {
-        SecurityAttributes(SECURITY_ATTRIBUTES {
-            nLength: size_of::<SECURITY_ATTRIBUTES>() as u32,
-            lpSecurityDescriptor: sd as *mut _,
-            bInheritHandle: FALSE,
-        })
-    }
-}
-
 pub struct Mutex<T>(Handle, T);
 impl<T> Mutex<T> {
     pub fn create(
         data: T,
-        mut security_attributes: Option<SecurityAttributes>,
+        security_attributes: Option<&SecurityAttributes>,
         name: &str,
     ) -> Result<Mutex<T>, InitError<T>> {
+        let mut security_attributes = SecurityAttributes::as_raw_opt(security_attributes);
         unsafe {
             let handle = CreateMutexW(
-                security_attributes
-                    .as_mut()
-                    .map(|x| &mut x.0 as *mut _)
-                    .unwrap_or(null_mut()),
+                SecurityAttributes::raw_opt_ptr(&mut security_attributes),
                 0,
                 name.to_wide_null().as_ptr(),
             );
@@ -85,6 +73,24 @@ impl<T> Mutex<T> {
             }
         }
     }
+    /// Like `wait`, but with the timeout as a `Duration` which is rounded up to whole milliseconds
+    pub fn wait_timeout(
+        &self,
+        timeout: Option<Duration>,
+    ) -> Result<MutexGuard<'_, T>, WaitError<'_, T>> {
+        self.wait(Some(timeout_ms(timeout)))
+    }
+    /// Closes this handle to the mutex and returns the data
+    pub fn into_inner(self) -> T {
+        let Mutex(handle, data) = self;
+        drop(handle);
+        data
+    }
+    /// No locking is needed since the mutable borrow guarantees this is the only reference to the
+    /// data through this handle.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.1
+    }
     pub fn try_clone(&self) -> Result<Mutex<T>, Error>
     where
         T: Clone,
@@ -114,6 +120,11 @@ where
         }
     }
 }
+impl<T> AsRawHandle for Mutex<T> {
+    fn as_raw_handle(&self) -> HANDLE {
+        *self.0
+    }
+}
 unsafe impl<T> Send for Mutex<T> where T: Send {}
 unsafe impl<T> Sync for Mutex<T> where T: Sync {}
 
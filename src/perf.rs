@@ -3,15 +3,73 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // All files in the project carrying such notice may not be copied, modified, or distributed
 // except according to those terms.
-use {k32};
+use std::{
+    mem::zeroed,
+    sync::atomic::{AtomicI64, Ordering::Relaxed},
+    time::Duration,
+};
+use winapi::um::{
+    profileapi::{QueryPerformanceCounter, QueryPerformanceFrequency},
+    winnt::LARGE_INTEGER,
+};
 
+/// The number of performance counter ticks per second.
+/// This is fixed at boot, so it is only queried once.
 pub fn frequency() -> i64 {
-    let mut freq = 0;
-    unsafe { k32::QueryPerformanceFrequency(&mut freq) };
+    // The frequency is never 0, so that marks it as not queried yet. Threads racing to query it
+    // all get the same value, so there is no need for anything stronger.
+    static FREQUENCY: AtomicI64 = AtomicI64::new(0);
+    let freq = FREQUENCY.load(Relaxed);
+    if freq != 0 {
+        return freq;
+    }
+    let freq = unsafe {
+        let mut freq: LARGE_INTEGER = zeroed();
+        QueryPerformanceFrequency(&mut freq);
+        *freq.QuadPart()
+    };
+    FREQUENCY.store(freq, Relaxed);
     freq
 }
+/// The current value of the performance counter in ticks.
 pub fn counter() -> i64 {
-    let mut count = 0;
-    unsafe { k32::QueryPerformanceCounter(&mut count) };
-    count
+    unsafe {
+        let mut count: LARGE_INTEGER = zeroed();
+        QueryPerformanceCounter(&mut count);
+        *count.QuadPart()
+    }
+}
+/// Converts a number of performance counter ticks into a `Duration`.
+pub fn ticks_to_duration(ticks: i64) -> Duration {
+    let ticks = ticks.max(0) as u64;
+    let freq = frequency() as u64;
+    let nanos = (ticks % freq) * 1_000_000_000 / freq;
+    Duration::new(ticks / freq, nanos as u32)
+}
+
+/// Measures elapsed time using the performance counter.
+#[derive(Clone, Copy, Debug)]
+pub struct Stopwatch {
+    start: i64,
+    lap: i64,
+}
+impl Stopwatch {
+    pub fn start() -> Stopwatch {
+        let now = counter();
+        Stopwatch {
+            start: now,
+            lap: now,
+        }
+    }
+    /// The time since the stopwatch was started
+    pub fn elapsed(&self) -> Duration {
+        ticks_to_duration(counter() - self.start)
+    }
+    /// The time since the previous lap, or since the stopwatch was started if this is the first.
+    pub fn lap(&mut self) -> Duration {
+        let now = counter();
+        let lap = ticks_to_duration(now - self.lap);
+        self.lap = now;
+        lap
+    }
 }
@@ -3,14 +3,289 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // All files in the project carrying such notice may not be copied, modified, or distributed
 // except according to those terms.
-use handle::{Handle};
+use error::{Error, Result};
+use file::{OpenMode, OpenOptions};
+use handle::Handle;
+use security::SecurityAttributes;
+use std::{
+    io::{self, Read, Write},
+    os::windows::io::{AsRawHandle, IntoRawHandle},
+    ptr::null_mut,
+};
+use wide::ToWide;
+use winapi::{
+    shared::{
+        minwindef::DWORD,
+        winerror::{ERROR_BROKEN_PIPE, ERROR_PIPE_BUSY, ERROR_PIPE_CONNECTED},
+    },
+    um::{
+        fileapi::{FlushFileBuffers, ReadFile, WriteFile},
+        handleapi::INVALID_HANDLE_VALUE,
+        namedpipeapi::{ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, WaitNamedPipeW},
+        winbase::{
+            PIPE_ACCESS_DUPLEX, PIPE_ACCESS_INBOUND, PIPE_ACCESS_OUTBOUND, PIPE_READMODE_BYTE,
+            PIPE_READMODE_MESSAGE, PIPE_TYPE_BYTE, PIPE_TYPE_MESSAGE, PIPE_UNLIMITED_INSTANCES,
+            PIPE_WAIT,
+        },
+        winnt::{GENERIC_READ, GENERIC_WRITE, HANDLE},
+    },
+};
 
-pub struct NamedPipe(Handle);
-impl NamedPipe {
-    //fn create(name: &[u16], access: Access, )
-}
+// Not defined by winapi
+const NMPWAIT_WAIT_FOREVER: DWORD = 0xffff_ffff;
+
+/// The direction data flows in, from the point of view of the server
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Access {
     Inbound,
     Outbound,
     Duplex,
 }
+impl Access {
+    fn to_raw(self) -> DWORD {
+        match self {
+            Access::Inbound => PIPE_ACCESS_INBOUND,
+            Access::Outbound => PIPE_ACCESS_OUTBOUND,
+            Access::Duplex => PIPE_ACCESS_DUPLEX,
+        }
+    }
+}
+
+/// Whether data is written to and read from the pipe as a stream of bytes or as messages
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PipeMode {
+    Byte,
+    Message,
+}
+impl PipeMode {
+    fn to_raw(self) -> DWORD {
+        match self {
+            PipeMode::Byte => PIPE_TYPE_BYTE | PIPE_READMODE_BYTE,
+            PipeMode::Message => PIPE_TYPE_MESSAGE | PIPE_READMODE_MESSAGE,
+        }
+    }
+}
+
+/// Options for `CreateNamedPipeW`.
+/// Defaults to a duplex byte mode pipe with unlimited instances and 4 KiB buffers.
+#[derive(Clone, Copy)]
+pub struct PipeOptions<'a> {
+    access: Access,
+    mode: PipeMode,
+    max_instances: u32,
+    out_buffer_size: u32,
+    in_buffer_size: u32,
+    default_timeout: u32,
+    security_attributes: Option<&'a SecurityAttributes<'a>>,
+}
+impl<'a> PipeOptions<'a> {
+    pub fn new() -> PipeOptions<'a> {
+        PipeOptions {
+            access: Access::Duplex,
+            mode: PipeMode::Byte,
+            max_instances: PIPE_UNLIMITED_INSTANCES,
+            out_buffer_size: 4096,
+            in_buffer_size: 4096,
+            default_timeout: 0,
+            security_attributes: None,
+        }
+    }
+    pub fn access(&mut self, access: Access) -> &mut PipeOptions<'a> {
+        self.access = access;
+        self
+    }
+    pub fn mode(&mut self, mode: PipeMode) -> &mut PipeOptions<'a> {
+        self.mode = mode;
+        self
+    }
+    /// Must be between 1 and 255, where 255 means unlimited
+    pub fn max_instances(&mut self, max_instances: u32) -> &mut PipeOptions<'a> {
+        self.max_instances = max_instances;
+        self
+    }
+    /// The sizes to reserve for the output and input buffers, in bytes
+    pub fn buffer_sizes(&mut self, out_size: u32, in_size: u32) -> &mut PipeOptions<'a> {
+        self.out_buffer_size = out_size;
+        self.in_buffer_size = in_size;
+        self
+    }
+    /// The default timeout in milliseconds used by `WaitNamedPipeW`, or 0 for 50 milliseconds
+    pub fn default_timeout(&mut self, default_timeout: u32) -> &mut PipeOptions<'a> {
+        self.default_timeout = default_timeout;
+        self
+    }
+    pub fn security_attributes(
+        &mut self,
+        security_attributes: &'a SecurityAttributes<'a>,
+    ) -> &mut PipeOptions<'a> {
+        self.security_attributes = Some(security_attributes);
+        self
+    }
+}
+impl<'a> Default for PipeOptions<'a> {
+    fn default() -> PipeOptions<'a> {
+        PipeOptions::new()
+    }
+}
+
+/// The server end of a named pipe instance
+pub struct PipeServer(Handle);
+impl PipeServer {
+    /// Creates an instance of the named pipe `name`, which must be of the form
+    /// `\\.\pipe\pipename`.
+    pub fn create(name: &str, options: &PipeOptions) -> Result<PipeServer> {
+        let mut security_attributes = SecurityAttributes::as_raw_opt(options.security_attributes);
+        let handle = unsafe {
+            CreateNamedPipeW(
+                name.to_wide_null().as_ptr(),
+                options.access.to_raw(),
+                options.mode.to_raw() | PIPE_WAIT,
+                options.max_instances,
+                options.out_buffer_size,
+                options.in_buffer_size,
+                options.default_timeout,
+                SecurityAttributes::raw_opt_ptr(&mut security_attributes),
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Error::last_result();
+        }
+        Ok(PipeServer(unsafe { Handle::new(handle) }))
+    }
+    /// Blocks until a client connects to this instance.
+    /// Succeeds immediately if a client connected before this was called.
+    pub fn connect(&self) -> Result<()> {
+        if unsafe { ConnectNamedPipe(*self.0, null_mut()) } == 0 {
+            let err = Error::last();
+            if err.code() != ERROR_PIPE_CONNECTED {
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+    /// Disconnects the current client so the instance can be connected to again.
+    pub fn disconnect(&self) -> Result<()> {
+        match unsafe { DisconnectNamedPipe(*self.0) } {
+            0 => Error::last_result(),
+            _ => Ok(()),
+        }
+    }
+    pub fn handle(&self) -> &Handle {
+        &self.0
+    }
+}
+impl Read for PipeServer {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        read(&self.0, buf)
+    }
+}
+impl Write for PipeServer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        write(&self.0, buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        flush(&self.0)
+    }
+}
+impl AsRawHandle for PipeServer {
+    fn as_raw_handle(&self) -> HANDLE {
+        self.0.as_raw_handle()
+    }
+}
+impl IntoRawHandle for PipeServer {
+    fn into_raw_handle(self) -> HANDLE {
+        self.0.into_raw_handle()
+    }
+}
+
+/// The client end of a named pipe
+pub struct PipeClient(Handle);
+impl PipeClient {
+    /// Connects to the named pipe `name` for reading and writing, waiting for an instance to
+    /// become available if they are all busy.
+    pub fn connect(name: &str) -> Result<PipeClient> {
+        loop {
+            let err = match OpenOptions::new()
+                .access(GENERIC_READ | GENERIC_WRITE)
+                .share_mode(0)
+                .flags_and_attributes(0)
+                .mode(OpenMode::OpenExisting)
+                .open(name)
+            {
+                Ok(file) => return Ok(PipeClient(file.into_handle())),
+                Err(err) => err,
+            };
+            if err.code() != ERROR_PIPE_BUSY {
+                return Err(err);
+            }
+            let name = name.to_wide_null();
+            if unsafe { WaitNamedPipeW(name.as_ptr(), NMPWAIT_WAIT_FOREVER) } == 0 {
+                return Error::last_result();
+            }
+        }
+    }
+    pub fn handle(&self) -> &Handle {
+        &self.0
+    }
+}
+impl Read for PipeClient {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        read(&self.0, buf)
+    }
+}
+impl Write for PipeClient {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        write(&self.0, buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        flush(&self.0)
+    }
+}
+impl AsRawHandle for PipeClient {
+    fn as_raw_handle(&self) -> HANDLE {
+        self.0.as_raw_handle()
+    }
+}
+impl IntoRawHandle for PipeClient {
+    fn into_raw_handle(self) -> HANDLE {
+        self.0.into_raw_handle()
+    }
+}
+
+fn read(handle: &Handle, buf: &mut [u8]) -> io::Result<usize> {
+    let len = buf.len().min(DWORD::MAX as usize) as DWORD;
+    let mut read = 0;
+    let res = unsafe {
+        ReadFile(
+            **handle,
+            buf.as_mut_ptr().cast(),
+            len,
+            &mut read,
+            null_mut(),
+        )
+    };
+    if res == 0 {
+        let err = Error::last();
+        // The other end closing the pipe is the end of the stream
+        if err.code() == ERROR_BROKEN_PIPE {
+            return Ok(0);
+        }
+        return Err(err.into());
+    }
+    Ok(read as usize)
+}
+fn write(handle: &Handle, buf: &[u8]) -> io::Result<usize> {
+    let len = buf.len().min(DWORD::MAX as usize) as DWORD;
+    let mut written = 0;
+    let res = unsafe { WriteFile(**handle, buf.as_ptr().cast(), len, &mut written, null_mut()) };
+    if res == 0 {
+        return Err(Error::last().into());
+    }
+    Ok(written as usize)
+}
+fn flush(handle: &Handle) -> io::Result<()> {
+    match unsafe { FlushFileBuffers(**handle) } {
+        0 => Err(Error::last().into()),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,109 @@
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// All files in the project carrying such notice may not be copied, modified, or distributed
+// except according to those terms.
+use error::{Error, Result};
+use handle::Handle;
+use std::{
+    os::windows::io::{AsRawHandle, FromRawHandle, IntoRawHandle},
+    process::Child,
+    time::Duration,
+};
+use winapi::{
+    shared::minwindef::{DWORD, FALSE},
+    um::{
+        minwinbase::STILL_ACTIVE,
+        processthreadsapi::{
+            GetCurrentProcess, GetExitCodeProcess, GetProcessId, OpenProcess, TerminateProcess,
+        },
+        winnt::HANDLE,
+    },
+};
+
+#[derive(Debug)]
+pub struct Process(Handle);
+impl Process {
+    /// Opens an existing process with the given `PROCESS_*` access rights.
+    pub fn open(pid: u32, access: DWORD) -> Result<Process> {
+        let handle = unsafe { OpenProcess(access, FALSE, pid) };
+        if handle.is_null() {
+            return Error::last_result();
+        }
+        Ok(Process(unsafe { Handle::new(handle) }))
+    }
+    /// The pseudo handle of the current process, which has all access rights and needs no
+    /// closing. Use `try_clone` for a real handle that can be given to other processes.
+    pub fn current() -> Process {
+        Process(unsafe { Handle::new(GetCurrentProcess()) })
+    }
+    pub fn try_clone(&self) -> Result<Process> {
+        unsafe { Handle::duplicate_from(*self.0).map(Process) }
+    }
+    /// The system wide identifier of the process
+    pub fn id(&self) -> Result<u32> {
+        match unsafe { GetProcessId(*self.0) } {
+            0 => Error::last_result(),
+            id => Ok(id),
+        }
+    }
+    /// Waits for the process to exit, returning its exit code, or `None` if the timeout elapsed
+    /// first. A timeout of `None` waits forever.
+    pub fn wait(&self, timeout: Option<Duration>) -> Result<Option<u32>> {
+        match timeout {
+            Some(timeout) => {
+                if !self.0.wait_timeout(timeout)? {
+                    return Ok(None);
+                }
+            }
+            None => self.0.wait()?,
+        }
+        // The process has definitely exited, so an exit code of STILL_ACTIVE is genuine
+        self.raw_exit_code().map(Some)
+    }
+    /// The exit code of the process, or `None` if it is still running.
+    /// A process which exits with `STILL_ACTIVE` (259) as its code is indistinguishable from one
+    /// which is still running, so use `wait` if that matters.
+    pub fn exit_code(&self) -> Result<Option<u32>> {
+        match self.raw_exit_code()? {
+            STILL_ACTIVE => Ok(None),
+            code => Ok(Some(code)),
+        }
+    }
+    fn raw_exit_code(&self) -> Result<u32> {
+        let mut code = 0;
+        match unsafe { GetExitCodeProcess(*self.0, &mut code) } {
+            0 => Error::last_result(),
+            _ => Ok(code),
+        }
+    }
+    /// Terminates the process unconditionally, giving it `code` as its exit code.
+    pub fn terminate(&self, code: u32) -> Result<()> {
+        match unsafe { TerminateProcess(*self.0, code) } {
+            0 => Error::last_result(),
+            _ => Ok(()),
+        }
+    }
+}
+impl From<Child> for Process {
+    fn from(o: Child) -> Process {
+        unsafe { Process::from_raw_handle(o.into_raw_handle()) }
+    }
+}
+impl AsRawHandle for Process {
+    fn as_raw_handle(&self) -> HANDLE {
+        self.0.as_raw_handle()
+    }
+}
+impl IntoRawHandle for Process {
+    fn into_raw_handle(self) -> HANDLE {
+        self.0.into_raw_handle()
+    }
+}
+impl FromRawHandle for Process {
+    unsafe fn from_raw_handle(handle: HANDLE) -> Process {
+        Process(Handle::from_raw_handle(handle))
+    }
+}
+unsafe impl Send for Process {}
+unsafe impl Sync for Process {}
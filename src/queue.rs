@@ -0,0 +1,192 @@
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// All files in the project carrying such notice may not be copied, modified, or distributed
+// except according to those terms.
+use error::{Error, Result};
+use handle::Handle;
+use std::{marker::PhantomData, os::windows::io::AsRawHandle, ptr::null_mut, time::Duration};
+use wait::timeout_ms;
+use winapi::{
+    shared::{
+        minwindef::DWORD,
+        winerror::{ERROR_INVALID_PARAMETER, WAIT_TIMEOUT},
+    },
+    um::{
+        handleapi::INVALID_HANDLE_VALUE,
+        ioapiset::{CreateIoCompletionPort, GetQueuedCompletionStatus, PostQueuedCompletionStatus},
+        minwinbase::OVERLAPPED,
+        winbase::INFINITE,
+    },
+};
+
+/// The completion key used for items sent with `Queue::post`.
+/// It cannot be used as the key for a registered handle.
+pub const POST_KEY: usize = !0;
+
+/// An I/O completion port which can both carry boxed values between threads and receive the
+/// completions of overlapped operations on handles registered with it.
+pub struct Queue<T>(Handle, PhantomData<T>);
+impl<T> Queue<T> {
+    pub fn new() -> Result<Queue<T>> {
+        let handle = unsafe { CreateIoCompletionPort(INVALID_HANDLE_VALUE, null_mut(), 0, 0) };
+        if handle.is_null() {
+            return Error::last_result();
+        }
+        unsafe { Ok(Queue(Handle::new(handle), PhantomData)) }
+    }
+    /// Associates a handle opened for overlapped I/O with this queue.
+    /// Overlapped operations on `handle` will complete on this queue with the given `key`.
+    pub fn register(&self, handle: &Handle, key: usize) -> Result<()> {
+        self.associate(handle, key)
+    }
+    /// Like `register` but for anything which has a raw handle, such as a `std::fs::File` or a
+    /// socket opened for overlapped I/O.
+    /// Fails with `ERROR_INVALID_PARAMETER` if `key` is `POST_KEY`.
+    pub fn associate<H: AsRawHandle>(&self, handle: &H, key: usize) -> Result<()> {
+        if key == POST_KEY {
+            return Err(Error::new(ERROR_INVALID_PARAMETER));
+        }
+        let res = unsafe { CreateIoCompletionPort(handle.as_raw_handle(), *self.0, key, 0) };
+        if res.is_null() {
+            return Error::last_result();
+        }
+        Ok(())
+    }
+    pub fn post(&self, thing: Box<T>) -> Result<()> {
+        let ptr = Box::into_raw(thing);
+        let res = unsafe { PostQueuedCompletionStatus(*self.0, 0, POST_KEY, ptr.cast()) };
+        if res == 0 {
+            // If it fails we still need to deallocate the thing
+            drop(unsafe { Box::from_raw(ptr) });
+            return Error::last_result();
+        }
+        Ok(())
+    }
+    /// Blocks until a completion arrives on the queue.
+    pub fn recv(&self) -> Result<Completion<T>> {
+        self.recv_ms(INFINITE)
+            .map(|completion| completion.expect("infinite wait timed out"))
+    }
+    /// Waits up to `timeout` for a completion, returning `None` if nothing arrived in time.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Option<Completion<T>>> {
+        self.recv_ms(timeout_ms(Some(timeout)))
+    }
+    fn recv_ms(&self, timeout: DWORD) -> Result<Option<Completion<T>>> {
+        let mut bytes = 0;
+        let mut key = 0;
+        let mut overlapped = null_mut();
+        let res = unsafe {
+            GetQueuedCompletionStatus(*self.0, &mut bytes, &mut key, &mut overlapped, timeout)
+        };
+        if overlapped.is_null() {
+            // Nothing was dequeued so this has to be a timeout or a failure of the call itself
+            let err = Error::last();
+            if err.code() == WAIT_TIMEOUT {
+                return Ok(None);
+            }
+            return Err(err);
+        }
+        if key == POST_KEY {
+            return Ok(Some(Completion::Posted(unsafe {
+                Box::from_raw(overlapped.cast())
+            })));
+        }
+        Ok(Some(Completion::Io {
+            key,
+            bytes,
+            overlapped,
+            result: match res {
+                0 => Error::last_result(),
+                _ => Ok(()),
+            },
+        }))
+    }
+}
+unsafe impl<T> Send for Queue<T> where T: Send {}
+unsafe impl<T> Sync for Queue<T> where T: Send {}
+
+/// A completion dequeued from a `Queue`.
+#[derive(Debug)]
+pub enum Completion<T> {
+    /// A value sent with `Queue::post`.
+    Posted(Box<T>),
+    /// The completion of an overlapped operation on a registered handle.
+    /// `result` is the error the operation failed with, if any.
+    Io {
+        key: usize,
+        bytes: u32,
+        overlapped: *mut OVERLAPPED,
+        result: Result<()>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Completion, Queue, POST_KEY};
+    use file::{OpenMode, OpenOptions};
+    use std::{env::temp_dir, fs, mem::zeroed, process};
+    use winapi::{
+        shared::winerror::{ERROR_INVALID_PARAMETER, ERROR_IO_PENDING},
+        um::{errhandlingapi::GetLastError, fileapi::ReadFile, winnt::GENERIC_READ},
+    };
+
+    #[test]
+    fn drains_file_read() {
+        let path = temp_dir().join(format!("wio-queue-{}", process::id()));
+        fs::write(&path, b"hello world").unwrap();
+        let file = OpenOptions::new()
+            .access(GENERIC_READ)
+            .mode(OpenMode::OpenExisting)
+            .open(&path)
+            .unwrap();
+        let queue = Queue::<()>::new().unwrap();
+        queue.register(file.handle(), 42).unwrap();
+        let mut buf = [0u8; 32];
+        let mut overlapped = unsafe { zeroed() };
+        let res = unsafe {
+            ReadFile(
+                **file.handle(),
+                buf.as_mut_ptr().cast(),
+                buf.len() as u32,
+                &mut 0,
+                &mut overlapped,
+            )
+        };
+        // Completions are queued whether the read finished synchronously or not
+        if res == 0 {
+            assert_eq!(unsafe { GetLastError() }, ERROR_IO_PENDING);
+        }
+        match queue.recv().unwrap() {
+            Completion::Io {
+                key,
+                bytes,
+                overlapped: ptr,
+                result,
+            } => {
+                assert_eq!(key, 42);
+                assert_eq!(bytes, 11);
+                assert_eq!(ptr, &mut overlapped as *mut _);
+                result.unwrap();
+            }
+            Completion::Posted(_) => panic!("expected an I/O completion"),
+        }
+        assert_eq!(&buf[..11], b"hello world");
+        drop(file);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn post_key_is_reserved() {
+        let queue = Queue::<()>::new().unwrap();
+        let path = temp_dir().join(format!("wio-queue-reserved-{}", process::id()));
+        let file = OpenOptions::new()
+            .mode(OpenMode::CreateAlways)
+            .open(&path)
+            .unwrap();
+        let err = queue.register(file.handle(), POST_KEY).unwrap_err();
+        assert_eq!(err.code(), ERROR_INVALID_PARAMETER);
+        drop(file);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// All files in the project carrying such notice may not be copied, modified, or distributed
+// except according to those terms.
+use error::{Error, Result};
+use security::SecurityAttributes;
+use std::{
+    ffi::{OsStr, OsString},
+    mem::size_of,
+    ptr::null_mut,
+};
+use wide::{from_wide_multi, to_wide_multi, FromWide, ToWide};
+use winapi::{
+    shared::{
+        minwindef::{DWORD, HKEY},
+        winerror::{ERROR_MORE_DATA, ERROR_SUCCESS, ERROR_UNSUPPORTED_TYPE},
+    },
+    um::{
+        winnt::{REG_DWORD, REG_EXPAND_SZ, REG_MULTI_SZ, REG_OPTION_NON_VOLATILE, REG_SZ},
+        winreg::{
+            RegCloseKey, RegCreateKeyExW, RegOpenKeyExW, RegQueryValueExW, RegSetValueExW, LSTATUS,
+        },
+    },
+};
+
+/// Registry functions return the error code instead of setting the last error.
+fn check(status: LSTATUS) -> Result<()> {
+    match status as DWORD {
+        ERROR_SUCCESS => Ok(()),
+        code => Err(Error::new(code)),
+    }
+}
+
+/// An open registry key which is closed on drop
+#[derive(Debug)]
+pub struct Key(HKEY);
+impl Key {
+    /// Opens an existing subkey of `root`, which is either a predefined key such as
+    /// `HKEY_CURRENT_USER` or the raw handle of another open key.
+    // HKEY is an opaque handle which is only passed through to the registry functions
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    pub fn open(root: HKEY, subkey: &str, access: DWORD) -> Result<Key> {
+        let subkey = subkey.to_wide_null();
+        let mut key = null_mut();
+        check(unsafe { RegOpenKeyExW(root, subkey.as_ptr(), 0, access, &mut key) })?;
+        Ok(Key(key))
+    }
+    /// Opens a subkey of `root`, creating it and any missing parents if it does not exist.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    pub fn create(
+        root: HKEY,
+        subkey: &str,
+        access: DWORD,
+        security_attributes: Option<&SecurityAttributes>,
+    ) -> Result<Key> {
+        let mut security_attributes = SecurityAttributes::as_raw_opt(security_attributes);
+        let mut key = null_mut();
+        check(unsafe {
+            RegCreateKeyExW(
+                root,
+                subkey.to_wide_null().as_ptr(),
+                0,
+                null_mut(),
+                REG_OPTION_NON_VOLATILE,
+                access,
+                SecurityAttributes::raw_opt_ptr(&mut security_attributes),
+                &mut key,
+                null_mut(),
+            )
+        })?;
+        Ok(Key(key))
+    }
+    /// Opens a subkey of this key.
+    pub fn open_subkey(&self, subkey: &str, access: DWORD) -> Result<Key> {
+        Key::open(self.0, subkey, access)
+    }
+    /// Reads a `REG_SZ` or `REG_EXPAND_SZ` value, where an empty name is the default value of
+    /// the key. Environment variables are not expanded.
+    pub fn get_string<N: AsRef<OsStr>>(&self, name: N) -> Result<OsString> {
+        match self.query(name.as_ref())? {
+            (REG_SZ, buf) | (REG_EXPAND_SZ, buf) => Ok(OsString::from_wide_null(&buf)),
+            _ => Err(Error::new(ERROR_UNSUPPORTED_TYPE)),
+        }
+    }
+    pub fn set_string<N: AsRef<OsStr>, V: AsRef<OsStr>>(&self, name: N, value: V) -> Result<()> {
+        self.set(name.as_ref(), REG_SZ, &value.to_wide_null())
+    }
+    /// Reads a `REG_MULTI_SZ` value.
+    pub fn get_multi_string<N: AsRef<OsStr>>(&self, name: N) -> Result<Vec<OsString>> {
+        match self.query(name.as_ref())? {
+            (REG_MULTI_SZ, buf) => Ok(from_wide_multi(&buf)),
+            _ => Err(Error::new(ERROR_UNSUPPORTED_TYPE)),
+        }
+    }
+    pub fn set_multi_string<N, I>(&self, name: N, values: I) -> Result<()>
+    where
+        N: AsRef<OsStr>,
+        I: IntoIterator,
+        I::Item: AsRef<OsStr>,
+    {
+        self.set(name.as_ref(), REG_MULTI_SZ, &to_wide_multi(values))
+    }
+    /// Reads a `REG_DWORD` value.
+    pub fn get_u32<N: AsRef<OsStr>>(&self, name: N) -> Result<u32> {
+        let mut kind = 0;
+        let mut value: DWORD = 0;
+        let mut len = size_of::<DWORD>() as DWORD;
+        check(unsafe {
+            RegQueryValueExW(
+                self.0,
+                name.to_wide_null().as_ptr(),
+                null_mut(),
+                &mut kind,
+                (&mut value as *mut DWORD).cast(),
+                &mut len,
+            )
+        })?;
+        if kind != REG_DWORD || len as usize != size_of::<DWORD>() {
+            return Err(Error::new(ERROR_UNSUPPORTED_TYPE));
+        }
+        Ok(value)
+    }
+    pub fn set_u32<N: AsRef<OsStr>>(&self, name: N, value: u32) -> Result<()> {
+        check(unsafe {
+            RegSetValueExW(
+                self.0,
+                name.to_wide_null().as_ptr(),
+                0,
+                REG_DWORD,
+                (&value as *const DWORD).cast(),
+                size_of::<DWORD>() as DWORD,
+            )
+        })
+    }
+    /// Queries the type and data of a value, growing the buffer until the data fits since the
+    /// value may change between probing the size and reading it.
+    // usize::div_ceil would need Rust 1.73
+    #[allow(clippy::manual_div_ceil)]
+    fn query(&self, name: &OsStr) -> Result<(DWORD, Vec<u16>)> {
+        let name = name.to_wide_null();
+        let mut kind = 0;
+        let mut len = 0;
+        check(unsafe {
+            RegQueryValueExW(
+                self.0,
+                name.as_ptr(),
+                null_mut(),
+                null_mut(),
+                null_mut(),
+                &mut len,
+            )
+        })?;
+        let mut buf: Vec<u16> = Vec::new();
+        loop {
+            // Round up to whole code units, leaving room for a nul in case the data lacks one
+            buf.resize((len as usize + 1) / 2 + 1, 0);
+            len = (buf.len() * 2) as DWORD;
+            let res = unsafe {
+                RegQueryValueExW(
+                    self.0,
+                    name.as_ptr(),
+                    null_mut(),
+                    &mut kind,
+                    buf.as_mut_ptr().cast(),
+                    &mut len,
+                )
+            };
+            if res as DWORD != ERROR_MORE_DATA {
+                check(res)?;
+                break;
+            }
+        }
+        buf.truncate((len as usize + 1) / 2);
+        Ok((kind, buf))
+    }
+    fn set(&self, name: &OsStr, kind: DWORD, data: &[u16]) -> Result<()> {
+        check(unsafe {
+            RegSetValueExW(
+                self.0,
+                name.to_wide_null().as_ptr(),
+                0,
+                kind,
+                data.as_ptr().cast(),
+                (data.len() * 2) as DWORD,
+            )
+        })
+    }
+    /// The raw handle of the key, which can be passed as the root of `Key::open`.
+    pub fn as_raw(&self) -> HKEY {
+        self.0
+    }
+}
+impl Drop for Key {
+    fn drop(&mut self) {
+        unsafe {
+            RegCloseKey(self.0);
+        }
+    }
+}
+unsafe impl Send for Key {}
+unsafe impl Sync for Key {}
@@ -0,0 +1,163 @@
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// All files in the project carrying such notice may not be copied, modified, or distributed
+// except according to those terms.
+use error::{Error, Result};
+use file::{OpenMode, OpenOptions};
+use handle::Handle;
+use std::{
+    ffi::{OsStr, OsString},
+    mem::size_of,
+    os::windows::ffi::OsStringExt,
+    ptr::null_mut,
+};
+use vsb::VariableSizedBox;
+use winapi::{
+    shared::{
+        minwindef::{DWORD, ULONG, USHORT},
+        winerror::ERROR_INVALID_REPARSE_DATA,
+    },
+    um::{
+        ioapiset::DeviceIoControl,
+        winbase::{FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT},
+        winioctl::FSCTL_GET_REPARSE_POINT,
+        winnt::{
+            FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE, IO_REPARSE_TAG_MOUNT_POINT,
+            IO_REPARSE_TAG_SYMLINK, MAXIMUM_REPARSE_DATA_BUFFER_SIZE,
+        },
+    },
+};
+
+// winapi does not define REPARSE_DATA_BUFFER since it lives in the DDK headers, so the header
+// and the two union members we decode are declared here.
+#[allow(dead_code)]
+#[repr(C)]
+struct ReparseDataHeader {
+    tag: DWORD,
+    data_length: USHORT,
+    reserved: USHORT,
+}
+#[allow(dead_code)]
+#[repr(C)]
+struct SymbolicLinkReparseBuffer {
+    substitute_name_offset: USHORT,
+    substitute_name_length: USHORT,
+    print_name_offset: USHORT,
+    print_name_length: USHORT,
+    flags: ULONG,
+}
+#[allow(dead_code)]
+#[repr(C)]
+struct MountPointReparseBuffer {
+    substitute_name_offset: USHORT,
+    substitute_name_length: USHORT,
+    print_name_offset: USHORT,
+    print_name_length: USHORT,
+}
+const SYMLINK_FLAG_RELATIVE: ULONG = 1;
+
+/// The decoded target of a reparse point, as its substitute name
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReparsePoint {
+    AbsoluteSymlink(OsString),
+    RelativeSymlink(OsString),
+    /// A junction or volume mount point
+    MountPoint(OsString),
+    /// A reparse point with some other tag
+    Other,
+}
+
+/// Reads the reparse point of `path` itself rather than whatever it points to.
+pub fn read<P: AsRef<OsStr>>(path: P) -> Result<ReparsePoint> {
+    let handle = OpenOptions::new()
+        .access(0)
+        .share_mode(FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE)
+        .flags_and_attributes(FILE_FLAG_OPEN_REPARSE_POINT | FILE_FLAG_BACKUP_SEMANTICS)
+        .mode(OpenMode::OpenExisting)
+        .open(path)?
+        .into_handle();
+    read_handle(&handle)
+}
+
+/// Reads the reparse point of a handle opened with `FILE_FLAG_OPEN_REPARSE_POINT`.
+pub fn read_handle(handle: &Handle) -> Result<ReparsePoint> {
+    let mut buf =
+        VariableSizedBox::<ReparseDataHeader>::new(MAXIMUM_REPARSE_DATA_BUFFER_SIZE as usize);
+    let mut bytes = 0;
+    let res = unsafe {
+        DeviceIoControl(
+            **handle,
+            FSCTL_GET_REPARSE_POINT,
+            null_mut(),
+            0,
+            buf.as_mut_ptr().cast(),
+            buf.len() as DWORD,
+            &mut bytes,
+            null_mut(),
+        )
+    };
+    if res == 0 {
+        return Error::last_result();
+    }
+    if (bytes as usize) < size_of::<ReparseDataHeader>() {
+        return Err(Error::new(ERROR_INVALID_REPARSE_DATA));
+    }
+    buf.resize(bytes as usize);
+    let header = size_of::<ReparseDataHeader>();
+    unsafe {
+        match buf.as_ref().tag {
+            IO_REPARSE_TAG_SYMLINK => {
+                let data = read_struct::<SymbolicLinkReparseBuffer>(&buf, header)?;
+                let name = path_name(
+                    &buf,
+                    header + size_of::<SymbolicLinkReparseBuffer>(),
+                    data.substitute_name_offset,
+                    data.substitute_name_length,
+                )?;
+                if data.flags & SYMLINK_FLAG_RELATIVE != 0 {
+                    Ok(ReparsePoint::RelativeSymlink(name))
+                } else {
+                    Ok(ReparsePoint::AbsoluteSymlink(name))
+                }
+            }
+            IO_REPARSE_TAG_MOUNT_POINT => {
+                let data = read_struct::<MountPointReparseBuffer>(&buf, header)?;
+                let name = path_name(
+                    &buf,
+                    header + size_of::<MountPointReparseBuffer>(),
+                    data.substitute_name_offset,
+                    data.substitute_name_length,
+                )?;
+                Ok(ReparsePoint::MountPoint(name))
+            }
+            _ => Ok(ReparsePoint::Other),
+        }
+    }
+}
+
+unsafe fn read_struct<U>(buf: &VariableSizedBox<ReparseDataHeader>, offset: usize) -> Result<&U> {
+    let ptr = buf.as_ptr().cast::<u8>().wrapping_add(offset).cast::<U>();
+    buf.try_slice_from_count(ptr, 1)
+        .map(|s| &s[0])
+        .ok_or_else(|| Error::new(ERROR_INVALID_REPARSE_DATA))
+}
+
+unsafe fn path_name(
+    buf: &VariableSizedBox<ReparseDataHeader>,
+    path_buffer: usize,
+    offset: USHORT,
+    length: USHORT,
+) -> Result<OsString> {
+    if offset & 1 != 0 {
+        return Err(Error::new(ERROR_INVALID_REPARSE_DATA));
+    }
+    let ptr = buf
+        .as_ptr()
+        .cast::<u8>()
+        .wrapping_add(path_buffer + offset as usize)
+        .cast::<u16>();
+    buf.try_slice_from_bytes(ptr, length as usize)
+        .map(OsString::from_wide)
+        .ok_or_else(|| Error::new(ERROR_INVALID_REPARSE_DATA))
+}
@@ -0,0 +1,859 @@
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// All files in the project carrying such notice may not be copied, modified, or distributed
+// except according to those terms.
+use error::{Error, Result};
+use handle::Handle;
+use std::{
+    ffi::{OsStr, OsString},
+    fmt::{Display, Formatter, Result as FmtResult},
+    marker::PhantomData,
+    mem::{size_of, size_of_val, zeroed},
+    os::windows::io::AsRawHandle,
+    ptr::{null, null_mut},
+    slice::from_raw_parts,
+};
+use vsb::VariableSizedBox;
+use wide::{from_wide_ptr, FromWide, ToWide};
+use winapi::{
+    shared::{
+        guiddef::GUID,
+        minwindef::{BOOL, DWORD, FALSE, TRUE},
+        sddl::{
+            ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW,
+            ConvertStringSidToSidW, SDDL_REVISION_1,
+        },
+        winerror::{
+            ERROR_INSUFFICIENT_BUFFER, ERROR_INVALID_ACL, ERROR_INVALID_SID,
+            ERROR_NOT_ALL_ASSIGNED, ERROR_SUCCESS,
+        },
+    },
+    um::{
+        accctrl::{
+            ACCESS_MODE, DENY_ACCESS, EXPLICIT_ACCESS_W, GRANT_ACCESS, NO_INHERITANCE,
+            NO_MULTIPLE_TRUSTEE, TRUSTEE_IS_SID, TRUSTEE_IS_UNKNOWN, TRUSTEE_W,
+        },
+        aclapi::SetEntriesInAclW,
+        minwinbase::{LMEM_FIXED, SECURITY_ATTRIBUTES},
+        processthreadsapi::{GetCurrentProcess, OpenProcessToken},
+        securitybaseapi::{
+            AdjustTokenPrivileges, CopySid, CreateWellKnownSid, EqualSid, GetAce,
+            GetAclInformation, GetLengthSid, GetSecurityDescriptorDacl, GetSecurityDescriptorGroup,
+            GetSecurityDescriptorOwner, GetSecurityDescriptorSacl, GetSidLengthRequired,
+            GetSidSubAuthority, GetSidSubAuthorityCount, GetTokenInformation,
+            ImpersonateLoggedOnUser, InitializeSecurityDescriptor, IsValidSid, MakeSelfRelativeSD,
+            RevertToSelf, SetSecurityDescriptorDacl, SetSecurityDescriptorOwner,
+        },
+        winbase::{LocalAlloc, LocalFree, LookupAccountSidW, LookupPrivilegeValueW},
+        winnt::{
+            AclSizeInformation, SidTypeAlias, SidTypeComputer, SidTypeDeletedAccount,
+            SidTypeDomain, SidTypeGroup, SidTypeInvalid, SidTypeLabel, SidTypeLogonSession,
+            SidTypeUser, SidTypeWellKnownGroup, TokenElevation, TokenIntegrityLevel, TokenUser,
+            WinBuiltinAdministratorsSid, ACCESS_ALLOWED_ACE, ACCESS_ALLOWED_ACE_TYPE,
+            ACCESS_ALLOWED_CALLBACK_ACE_TYPE, ACCESS_ALLOWED_CALLBACK_OBJECT_ACE_TYPE,
+            ACCESS_ALLOWED_OBJECT_ACE, ACCESS_ALLOWED_OBJECT_ACE_TYPE, ACCESS_DENIED_ACE_TYPE,
+            ACCESS_DENIED_CALLBACK_ACE_TYPE, ACCESS_DENIED_CALLBACK_OBJECT_ACE_TYPE,
+            ACCESS_DENIED_OBJECT_ACE_TYPE, ACCESS_MASK, ACE_HEADER,
+            ACE_INHERITED_OBJECT_TYPE_PRESENT, ACE_OBJECT_TYPE_PRESENT, ACL, ACL_SIZE_INFORMATION,
+            HANDLE, LUID_AND_ATTRIBUTES, PACL, PSECURITY_DESCRIPTOR, PSID, SECURITY_DESCRIPTOR,
+            SECURITY_DESCRIPTOR_REVISION, SECURITY_MANDATORY_HIGH_RID, SECURITY_MANDATORY_LOW_RID,
+            SECURITY_MANDATORY_MEDIUM_PLUS_RID, SECURITY_MANDATORY_MEDIUM_RID,
+            SECURITY_MANDATORY_SYSTEM_RID, SECURITY_MANDATORY_UNTRUSTED_RID, SECURITY_MAX_SID_SIZE,
+            SE_PRIVILEGE_ENABLED, SID_NAME_USE, SYSTEM_ACCESS_FILTER_ACE_TYPE,
+            SYSTEM_ALARM_ACE_TYPE, SYSTEM_ALARM_CALLBACK_ACE_TYPE,
+            SYSTEM_ALARM_CALLBACK_OBJECT_ACE_TYPE, SYSTEM_ALARM_OBJECT_ACE_TYPE,
+            SYSTEM_AUDIT_ACE_TYPE, SYSTEM_AUDIT_CALLBACK_ACE_TYPE,
+            SYSTEM_AUDIT_CALLBACK_OBJECT_ACE_TYPE, SYSTEM_AUDIT_OBJECT_ACE_TYPE,
+            SYSTEM_MANDATORY_LABEL_ACE_TYPE, SYSTEM_PROCESS_TRUST_LABEL_ACE_TYPE,
+            SYSTEM_RESOURCE_ATTRIBUTE_ACE_TYPE, SYSTEM_SCOPED_POLICY_ID_ACE_TYPE, TOKEN_ELEVATION,
+            TOKEN_INFORMATION_CLASS, TOKEN_MANDATORY_LABEL, TOKEN_PRIVILEGES, TOKEN_USER,
+            WELL_KNOWN_SID_TYPE,
+        },
+    },
+};
+
+/// An owned security identifier allocated with `LocalAlloc`.
+pub struct Sid(PSID);
+impl Sid {
+    /// Takes ownership of a SID which must have been allocated with `LocalAlloc`.
+    pub unsafe fn from_raw(sid: PSID) -> Sid {
+        Sid(sid)
+    }
+    /// Parses the string form of a SID such as `S-1-5-32-544`, or an SDDL abbreviation such as
+    /// `BA`.
+    pub fn from_string<S: AsRef<OsStr>>(s: S) -> Result<Sid> {
+        let mut sid = null_mut();
+        let res = unsafe { ConvertStringSidToSidW(s.to_wide_null().as_ptr(), &mut sid) };
+        if res == 0 {
+            return Error::last_result();
+        }
+        Ok(Sid(sid))
+    }
+    /// Creates a well known SID such as `WinLocalSystemSid`. Only SIDs which do not belong to a
+    /// domain are supported.
+    pub fn well_known(kind: WELL_KNOWN_SID_TYPE) -> Result<Sid> {
+        let mut len = SECURITY_MAX_SID_SIZE as DWORD;
+        let ptr = unsafe { LocalAlloc(LMEM_FIXED, len as usize) };
+        if ptr.is_null() {
+            return Error::last_result();
+        }
+        let sid = Sid(ptr);
+        match unsafe { CreateWellKnownSid(kind, null_mut(), sid.0, &mut len) } {
+            0 => Error::last_result(),
+            _ => Ok(sid),
+        }
+    }
+    /// The `BUILTIN\Administrators` group, `S-1-5-32-544`.
+    pub fn admin_group() -> Result<Sid> {
+        Sid::well_known(WinBuiltinAdministratorsSid)
+    }
+    /// Copies a SID out of a byte buffer, such as one produced by `as_bytes`.
+    /// Fails with `ERROR_INVALID_SID` if the bytes do not form a valid SID or are not exactly as
+    /// long as it.
+    /// # Safety
+    /// This places no requirements on the caller beyond `bytes` being a valid slice. It is
+    /// `unsafe` only because the API was specified that way. The bytes are copied and checked
+    /// against the length their sub-authority count implies before `IsValidSid` reads them, so
+    /// malformed input is reported as an error rather than read out of bounds.
+    pub unsafe fn from_bytes(bytes: &[u8]) -> Result<Sid> {
+        // The sub-authority count is the second byte, and IsValidSid trusts it, so make sure the
+        // buffer covers every sub-authority before handing it over
+        if bytes.len() < 2 || bytes.len() < GetSidLengthRequired(bytes[1]) as usize {
+            return Err(Error::new(ERROR_INVALID_SID));
+        }
+        let ptr = LocalAlloc(LMEM_FIXED, bytes.len());
+        if ptr.is_null() {
+            return Error::last_result();
+        }
+        bytes
+            .as_ptr()
+            .copy_to_nonoverlapping(ptr.cast(), bytes.len());
+        let sid = Sid(ptr);
+        if !sid.is_valid() || sid.len() != bytes.len() {
+            return Err(Error::new(ERROR_INVALID_SID));
+        }
+        Ok(sid)
+    }
+    /// Formats the SID in its string form such as `S-1-5-32-544`.
+    pub fn to_string(&self) -> Result<String> {
+        let mut ptr = null_mut();
+        let res = unsafe { ConvertSidToStringSidW(self.0, &mut ptr) };
+        if res == 0 {
+            return Error::last_result();
+        }
+        let string = unsafe { from_wide_ptr(ptr) };
+        unsafe { LocalFree(ptr.cast()) };
+        Ok(string)
+    }
+    /// Looks up the account name and domain of the SID on the given system, or on the local
+    /// system if `None`.
+    pub fn lookup_account(&self, system: Option<&OsStr>) -> Result<AccountName> {
+        let system = system.map(|s| s.to_wide_null());
+        let system = system.as_ref().map_or(null(), |s| s.as_ptr());
+        let mut name: Vec<u16> = vec![0; 64];
+        let mut domain: Vec<u16> = vec![0; 64];
+        loop {
+            let mut name_len = name.len() as DWORD;
+            let mut domain_len = domain.len() as DWORD;
+            let mut kind = 0;
+            let res = unsafe {
+                LookupAccountSidW(
+                    system,
+                    self.0,
+                    name.as_mut_ptr(),
+                    &mut name_len,
+                    domain.as_mut_ptr(),
+                    &mut domain_len,
+                    &mut kind,
+                )
+            };
+            if res != 0 {
+                // On success the lengths exclude the nul terminator
+                return Ok(AccountName {
+                    name: OsString::from_wide(&name[..name_len as usize]),
+                    domain: OsString::from_wide(&domain[..domain_len as usize]),
+                    kind: SidNameUse::from_raw(kind),
+                });
+            }
+            let err = Error::last();
+            if err.code() != ERROR_INSUFFICIENT_BUFFER {
+                return Err(err);
+            }
+            // On failure the lengths are the required sizes including the nul terminator
+            let name_len = (name_len as usize).max(name.len());
+            let domain_len = (domain_len as usize).max(domain.len());
+            name.resize(name_len, 0);
+            domain.resize(domain_len, 0);
+        }
+    }
+    /// Copies the SID into a new allocation.
+    /// Fails with `ERROR_INVALID_SID` if this SID is not valid.
+    pub fn try_clone(&self) -> Result<Sid> {
+        unsafe { Sid::copy_from(self.0) }
+    }
+    // Copies a SID this type does not own
+    unsafe fn copy_from(psid: PSID) -> Result<Sid> {
+        if IsValidSid(psid) == 0 {
+            return Err(Error::new(ERROR_INVALID_SID));
+        }
+        let len = GetLengthSid(psid);
+        let ptr = LocalAlloc(LMEM_FIXED, len as usize);
+        if ptr.is_null() {
+            return Error::last_result();
+        }
+        let sid = Sid(ptr);
+        match CopySid(len, sid.0, psid) {
+            0 => Error::last_result(),
+            _ => Ok(sid),
+        }
+    }
+    pub fn is_valid(&self) -> bool {
+        unsafe { IsValidSid(self.0) != 0 }
+    }
+    /// The length of the SID in bytes.
+    pub fn len(&self) -> usize {
+        unsafe { GetLengthSid(self.0) as usize }
+    }
+    /// The raw bytes of the SID, suitable for persisting and passing to `from_bytes`.
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { from_raw_parts(self.0.cast(), self.len()) }
+    }
+    pub fn as_ptr(&self) -> PSID {
+        self.0
+    }
+}
+/// The account a SID refers to, as found by `Sid::lookup_account`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AccountName {
+    pub name: OsString,
+    pub domain: OsString,
+    pub kind: SidNameUse,
+}
+/// The kind of account a SID refers to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SidNameUse {
+    User,
+    Group,
+    Domain,
+    Alias,
+    WellKnownGroup,
+    DeletedAccount,
+    Invalid,
+    Unknown,
+    Computer,
+    Label,
+    LogonSession,
+}
+impl SidNameUse {
+    #[allow(non_upper_case_globals)]
+    fn from_raw(kind: SID_NAME_USE) -> SidNameUse {
+        match kind {
+            SidTypeUser => SidNameUse::User,
+            SidTypeGroup => SidNameUse::Group,
+            SidTypeDomain => SidNameUse::Domain,
+            SidTypeAlias => SidNameUse::Alias,
+            SidTypeWellKnownGroup => SidNameUse::WellKnownGroup,
+            SidTypeDeletedAccount => SidNameUse::DeletedAccount,
+            SidTypeInvalid => SidNameUse::Invalid,
+            SidTypeComputer => SidNameUse::Computer,
+            SidTypeLabel => SidNameUse::Label,
+            SidTypeLogonSession => SidNameUse::LogonSession,
+            _ => SidNameUse::Unknown,
+        }
+    }
+}
+impl Clone for Sid {
+    /// # Panics
+    /// Panics if the SID is not valid or the copy cannot be allocated.
+    fn clone(&self) -> Sid {
+        self.try_clone().expect("failed to clone SID")
+    }
+}
+/// Invalid SIDs are never equal to anything, including themselves.
+impl PartialEq for Sid {
+    fn eq(&self, other: &Sid) -> bool {
+        self.is_valid() && other.is_valid() && unsafe { EqualSid(self.0, other.0) != 0 }
+    }
+}
+impl Display for Sid {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self.to_string() {
+            Ok(s) => f.write_str(&s),
+            Err(_) => f.write_str("<invalid sid>"),
+        }
+    }
+}
+impl Drop for Sid {
+    fn drop(&mut self) {
+        unsafe { LocalFree(self.0) };
+    }
+}
+unsafe impl Send for Sid {}
+unsafe impl Sync for Sid {}
+
+/// An owned self-relative security descriptor allocated with `LocalAlloc`.
+/// A self-relative descriptor stores its owner, group and ACLs in one contiguous block after the
+/// header, as opposed to an absolute descriptor which points to them wherever they live. Being
+/// a single block, it can be copied and persisted as bytes.
+pub struct SecurityDescriptor(PSECURITY_DESCRIPTOR);
+impl SecurityDescriptor {
+    /// Takes ownership of a descriptor which must have been allocated with `LocalAlloc`.
+    pub unsafe fn from_raw(sd: PSECURITY_DESCRIPTOR) -> SecurityDescriptor {
+        SecurityDescriptor(sd)
+    }
+    /// Parses a descriptor in the Security Descriptor Definition Language, such as
+    /// `D:(A;;GA;;;BA)` which grants full access to administrators only.
+    pub fn from_sddl<S: AsRef<OsStr>>(sddl: S) -> Result<SecurityDescriptor> {
+        let mut sd = null_mut();
+        let res = unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                sddl.to_wide_null().as_ptr(),
+                SDDL_REVISION_1.into(),
+                &mut sd,
+                null_mut(),
+            )
+        };
+        if res == 0 {
+            return Error::last_result();
+        }
+        Ok(SecurityDescriptor(sd))
+    }
+    /// The discretionary ACL, which controls access to the object.
+    /// `None` means there is no DACL, which grants everyone full access, whereas an empty DACL
+    /// grants no access at all.
+    pub fn dacl(&self) -> Result<Option<&Acl>> {
+        let (mut present, mut defaulted, mut acl) = (0, 0, null_mut());
+        let res =
+            unsafe { GetSecurityDescriptorDacl(self.0, &mut present, &mut acl, &mut defaulted) };
+        if res == 0 {
+            return Error::last_result();
+        }
+        Ok(unsafe { Acl::from_present(present, acl) })
+    }
+    /// The system ACL, which controls auditing.
+    pub fn sacl(&self) -> Result<Option<&Acl>> {
+        let (mut present, mut defaulted, mut acl) = (0, 0, null_mut());
+        let res =
+            unsafe { GetSecurityDescriptorSacl(self.0, &mut present, &mut acl, &mut defaulted) };
+        if res == 0 {
+            return Error::last_result();
+        }
+        Ok(unsafe { Acl::from_present(present, acl) })
+    }
+    /// A copy of the owner SID, or `None` if the descriptor has no owner.
+    pub fn owner(&self) -> Result<Option<Sid>> {
+        let (mut sid, mut defaulted) = (null_mut(), 0);
+        let res = unsafe { GetSecurityDescriptorOwner(self.0, &mut sid, &mut defaulted) };
+        if res == 0 {
+            return Error::last_result();
+        }
+        unsafe { copy_sid_opt(sid) }
+    }
+    /// A copy of the primary group SID, or `None` if the descriptor has no primary group.
+    pub fn group(&self) -> Result<Option<Sid>> {
+        let (mut sid, mut defaulted) = (null_mut(), 0);
+        let res = unsafe { GetSecurityDescriptorGroup(self.0, &mut sid, &mut defaulted) };
+        if res == 0 {
+            return Error::last_result();
+        }
+        unsafe { copy_sid_opt(sid) }
+    }
+    pub fn as_ptr(&self) -> PSECURITY_DESCRIPTOR {
+        self.0
+    }
+}
+impl Drop for SecurityDescriptor {
+    fn drop(&mut self) {
+        unsafe { LocalFree(self.0) };
+    }
+}
+unsafe impl Send for SecurityDescriptor {}
+unsafe impl Sync for SecurityDescriptor {}
+
+unsafe fn copy_sid_opt(psid: PSID) -> Result<Option<Sid>> {
+    if psid.is_null() {
+        return Ok(None);
+    }
+    Sid::copy_from(psid).map(Some)
+}
+
+/// Builds a security descriptor from a list of access rules, for the common case where writing
+/// SDDL by hand would be awkward, such as when the SIDs are only known at runtime.
+/// The entries apply to the object itself and are not inherited by child objects.
+#[derive(Default)]
+pub struct SecurityDescriptorBuilder<'a> {
+    entries: Vec<(ACCESS_MODE, &'a Sid, ACCESS_MASK)>,
+    owner: Option<&'a Sid>,
+}
+impl<'a> SecurityDescriptorBuilder<'a> {
+    pub fn new() -> SecurityDescriptorBuilder<'a> {
+        SecurityDescriptorBuilder::default()
+    }
+    /// Grants the access rights in `mask` to `sid`, in addition to any it is already granted.
+    pub fn allow(&mut self, sid: &'a Sid, mask: ACCESS_MASK) -> &mut SecurityDescriptorBuilder<'a> {
+        self.entries.push((GRANT_ACCESS, sid, mask));
+        self
+    }
+    /// Denies the access rights in `mask` to `sid`. Denials take precedence over grants
+    /// regardless of the order they were added in.
+    pub fn deny(&mut self, sid: &'a Sid, mask: ACCESS_MASK) -> &mut SecurityDescriptorBuilder<'a> {
+        self.entries.push((DENY_ACCESS, sid, mask));
+        self
+    }
+    pub fn owner(&mut self, sid: &'a Sid) -> &mut SecurityDescriptorBuilder<'a> {
+        self.owner = Some(sid);
+        self
+    }
+    /// Builds a self-relative descriptor which can be used with `SecurityAttributes::new`.
+    /// With no entries the DACL is empty, which grants no access at all.
+    pub fn build(&self) -> Result<SecurityDescriptor> {
+        let mut entries: Vec<EXPLICIT_ACCESS_W> = self
+            .entries
+            .iter()
+            .map(|&(mode, sid, mask)| EXPLICIT_ACCESS_W {
+                grfAccessPermissions: mask,
+                grfAccessMode: mode,
+                grfInheritance: NO_INHERITANCE,
+                Trustee: TRUSTEE_W {
+                    pMultipleTrustee: null_mut(),
+                    MultipleTrusteeOperation: NO_MULTIPLE_TRUSTEE,
+                    TrusteeForm: TRUSTEE_IS_SID,
+                    TrusteeType: TRUSTEE_IS_UNKNOWN,
+                    ptstrName: sid.as_ptr().cast(),
+                },
+            })
+            .collect();
+        let mut acl = null_mut();
+        // Like the registry functions, this returns the error code rather than setting it
+        let res = unsafe {
+            SetEntriesInAclW(
+                entries.len() as u32,
+                entries.as_mut_ptr(),
+                null_mut(),
+                &mut acl,
+            )
+        };
+        if res != ERROR_SUCCESS {
+            return Err(Error::new(res));
+        }
+        let sd = unsafe { self.make_self_relative(acl) };
+        unsafe { LocalFree(acl.cast()) };
+        sd
+    }
+    /// Assembles an absolute descriptor pointing at the ACL and owner, then copies everything
+    /// into a single allocation.
+    unsafe fn make_self_relative(&self, acl: PACL) -> Result<SecurityDescriptor> {
+        let mut absolute: SECURITY_DESCRIPTOR = zeroed();
+        let psd: PSECURITY_DESCRIPTOR = (&mut absolute as *mut SECURITY_DESCRIPTOR).cast();
+        if InitializeSecurityDescriptor(psd, SECURITY_DESCRIPTOR_REVISION) == 0 {
+            return Error::last_result();
+        }
+        if SetSecurityDescriptorDacl(psd, TRUE, acl, FALSE) == 0 {
+            return Error::last_result();
+        }
+        if let Some(owner) = self.owner {
+            if SetSecurityDescriptorOwner(psd, owner.as_ptr(), FALSE) == 0 {
+                return Error::last_result();
+            }
+        }
+        let mut len = 0;
+        if MakeSelfRelativeSD(psd, null_mut(), &mut len) == 0 {
+            let err = Error::last();
+            if err.code() != ERROR_INSUFFICIENT_BUFFER {
+                return Err(err);
+            }
+        }
+        let ptr = LocalAlloc(LMEM_FIXED, len as usize);
+        if ptr.is_null() {
+            return Error::last_result();
+        }
+        // Take ownership right away so the buffer is freed if the copy fails
+        let sd = SecurityDescriptor(ptr);
+        if MakeSelfRelativeSD(psd, sd.0, &mut len) == 0 {
+            return Error::last_result();
+        }
+        Ok(sd)
+    }
+}
+
+/// An access control list, borrowed from the security descriptor or buffer holding it.
+#[repr(transparent)]
+pub struct Acl(ACL);
+impl Acl {
+    /// # Safety
+    /// `acl` must be null or point to a valid ACL which outlives `'a`.
+    pub unsafe fn from_ptr<'a>(acl: *const ACL) -> Option<&'a Acl> {
+        (acl as *const Acl).as_ref()
+    }
+    unsafe fn from_present<'a>(present: BOOL, acl: PACL) -> Option<&'a Acl> {
+        if present == 0 {
+            return None;
+        }
+        Acl::from_ptr(acl)
+    }
+    /// The number of entries in the list
+    pub fn len(&self) -> Result<u32> {
+        let mut info: ACL_SIZE_INFORMATION = unsafe { zeroed() };
+        let res = unsafe {
+            GetAclInformation(
+                self.as_ptr() as PACL,
+                (&mut info as *mut ACL_SIZE_INFORMATION).cast(),
+                size_of_val(&info) as DWORD,
+                AclSizeInformation,
+            )
+        };
+        if res == 0 {
+            return Error::last_result();
+        }
+        Ok(info.AceCount)
+    }
+    pub fn entries(&self) -> AceIter<'_> {
+        AceIter {
+            acl: self,
+            index: 0,
+            len: self.len(),
+        }
+    }
+    pub fn as_ptr(&self) -> *const ACL {
+        &self.0
+    }
+}
+impl<'a> IntoIterator for &'a Acl {
+    type Item = Result<Ace<'a>>;
+    type IntoIter = AceIter<'a>;
+    fn into_iter(self) -> AceIter<'a> {
+        self.entries()
+    }
+}
+/// An iterator over the entries of an `Acl`
+/// If an entry cannot be read the error is yielded and iteration stops, so a malformed list is
+/// never mistaken for a shorter one.
+pub struct AceIter<'a> {
+    acl: &'a Acl,
+    index: u32,
+    len: Result<u32>,
+}
+impl<'a> Iterator for AceIter<'a> {
+    type Item = Result<Ace<'a>>;
+    fn next(&mut self) -> Option<Result<Ace<'a>>> {
+        let len = match self.len {
+            Ok(len) => len,
+            Err(err) => {
+                self.len = Ok(0);
+                return Some(Err(err));
+            }
+        };
+        if self.index >= len {
+            return None;
+        }
+        let mut ace = null_mut();
+        let res = unsafe { GetAce(self.acl.as_ptr() as PACL, self.index, &mut ace) };
+        if res == 0 {
+            self.index = len;
+            return Some(Error::last_result());
+        }
+        let header = unsafe { &*(ace as *const ACE_HEADER) };
+        // Every kind of entry has a mask after the header, which `Ace::mask` relies on
+        if usize::from(header.AceSize) < size_of::<ACE_HEADER>() + size_of::<ACCESS_MASK>() {
+            self.index = len;
+            return Some(Err(Error::new(ERROR_INVALID_ACL)));
+        }
+        self.index += 1;
+        Some(Ok(Ace(header)))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = match self.len {
+            Ok(len) => (len - self.index) as usize,
+            Err(_) => 1,
+        };
+        (len, Some(len))
+    }
+}
+/// An access control entry borrowed from an `Acl`
+#[derive(Clone, Copy)]
+pub struct Ace<'a>(&'a ACE_HEADER);
+impl<'a> Ace<'a> {
+    /// The kind of entry, such as `ACCESS_ALLOWED_ACE_TYPE`
+    pub fn ace_type(&self) -> u8 {
+        self.0.AceType
+    }
+    /// Inheritance and auditing flags such as `CONTAINER_INHERIT_ACE`
+    pub fn flags(&self) -> u8 {
+        self.0.AceFlags
+    }
+    /// The access rights the entry allows, denies or audits
+    pub fn mask(&self) -> ACCESS_MASK {
+        // Every kind of entry puts the mask right after the header, and the iterator checked
+        // that the entry is large enough to hold it
+        unsafe { (*(self.0 as *const ACE_HEADER as *const ACCESS_ALLOWED_ACE)).Mask }
+    }
+    /// A copy of the SID the entry applies to, or `None` for kinds of entries this doesn't know
+    /// how to find the SID in.
+    /// Fails with `ERROR_INVALID_ACL` if the SID does not fit within the entry.
+    pub fn sid(&self) -> Result<Option<Sid>> {
+        let bytes = unsafe {
+            from_raw_parts(
+                self.0 as *const ACE_HEADER as *const u8,
+                usize::from(self.0.AceSize),
+            )
+        };
+        let offset = match self.0.AceType {
+            ACCESS_ALLOWED_ACE_TYPE
+            | ACCESS_DENIED_ACE_TYPE
+            | SYSTEM_AUDIT_ACE_TYPE
+            | SYSTEM_ALARM_ACE_TYPE
+            | ACCESS_ALLOWED_CALLBACK_ACE_TYPE
+            | ACCESS_DENIED_CALLBACK_ACE_TYPE
+            | SYSTEM_AUDIT_CALLBACK_ACE_TYPE
+            | SYSTEM_ALARM_CALLBACK_ACE_TYPE
+            | SYSTEM_MANDATORY_LABEL_ACE_TYPE
+            | SYSTEM_RESOURCE_ATTRIBUTE_ACE_TYPE
+            | SYSTEM_SCOPED_POLICY_ID_ACE_TYPE
+            | SYSTEM_PROCESS_TRUST_LABEL_ACE_TYPE
+            | SYSTEM_ACCESS_FILTER_ACE_TYPE => 8,
+            ACCESS_ALLOWED_OBJECT_ACE_TYPE
+            | ACCESS_DENIED_OBJECT_ACE_TYPE
+            | SYSTEM_AUDIT_OBJECT_ACE_TYPE
+            | SYSTEM_ALARM_OBJECT_ACE_TYPE
+            | ACCESS_ALLOWED_CALLBACK_OBJECT_ACE_TYPE
+            | ACCESS_DENIED_CALLBACK_OBJECT_ACE_TYPE
+            | SYSTEM_AUDIT_CALLBACK_OBJECT_ACE_TYPE
+            | SYSTEM_ALARM_CALLBACK_OBJECT_ACE_TYPE => {
+                // The object type GUIDs are only present if flagged as such
+                if bytes.len() < 12 {
+                    return Err(Error::new(ERROR_INVALID_ACL));
+                }
+                let ace = unsafe { &*(bytes.as_ptr() as *const ACCESS_ALLOWED_OBJECT_ACE) };
+                let mut offset = 12;
+                if ace.Flags & ACE_OBJECT_TYPE_PRESENT != 0 {
+                    offset += size_of::<GUID>();
+                }
+                if ace.Flags & ACE_INHERITED_OBJECT_TYPE_PRESENT != 0 {
+                    offset += size_of::<GUID>();
+                }
+                offset
+            }
+            _ => return Ok(None),
+        };
+        // Callback entries carry application data after the SID, so only take as many bytes as
+        // its sub-authority count calls for
+        let sid = bytes.get(offset..).unwrap_or(&[]);
+        if sid.len() < 2 {
+            return Err(Error::new(ERROR_INVALID_ACL));
+        }
+        let len = unsafe { GetSidLengthRequired(sid[1]) } as usize;
+        match sid.get(..len) {
+            Some(sid) => unsafe { Sid::from_bytes(sid) }.map(Some),
+            None => Err(Error::new(ERROR_INVALID_ACL)),
+        }
+    }
+}
+
+/// The security descriptor and inheritability of a newly created object.
+pub struct SecurityAttributes<'a>(SECURITY_ATTRIBUTES, PhantomData<&'a SecurityDescriptor>);
+impl<'a> SecurityAttributes<'a> {
+    pub fn new(sd: &'a SecurityDescriptor, inheritable: bool) -> SecurityAttributes<'a> {
+        let mut attributes = unsafe { SecurityAttributes::from_raw(sd.0.cast()) };
+        attributes.0.bInheritHandle = inheritable as BOOL;
+        attributes
+    }
+    /// The security descriptor is borrowed, so it must outlive every use of the attributes.
+    pub unsafe fn from_raw(sd: *mut SECURITY_DESCRIPTOR) -> SecurityAttributes<'a> {
+        SecurityAttributes(
+            SECURITY_ATTRIBUTES {
+                nLength: size_of::<SECURITY_ATTRIBUTES>() as u32,
+                lpSecurityDescriptor: sd.cast(),
+                bInheritHandle: FALSE,
+            },
+            PhantomData,
+        )
+    }
+    /// A copy of the raw structure, for passing to functions that take a mutable pointer.
+    pub fn get_raw(&self) -> SECURITY_ATTRIBUTES {
+        self.0
+    }
+    pub fn as_ptr(&self) -> *const SECURITY_ATTRIBUTES {
+        &self.0
+    }
+    /// Copies optional attributes so they can be passed with `raw_opt_ptr`, since the functions
+    /// creating objects take a mutable pointer.
+    pub(crate) fn as_raw_opt(
+        attributes: Option<&SecurityAttributes<'_>>,
+    ) -> Option<SECURITY_ATTRIBUTES> {
+        attributes.map(SecurityAttributes::get_raw)
+    }
+    /// A pointer to the copy made by `as_raw_opt`, or null if there are no attributes.
+    pub(crate) fn raw_opt_ptr(raw: &mut Option<SECURITY_ATTRIBUTES>) -> *mut SECURITY_ATTRIBUTES {
+        raw.as_mut().map_or(null_mut(), |raw| raw as *mut _)
+    }
+}
+
+/// An access token, which describes the security context of a process or thread.
+#[derive(Debug)]
+pub struct Token(Handle);
+impl Token {
+    /// Opens the token of the current process with the given `TOKEN_*` access rights.
+    pub fn open_process_token(access: DWORD) -> Result<Token> {
+        let mut handle = null_mut();
+        match unsafe { OpenProcessToken(GetCurrentProcess(), access, &mut handle) } {
+            0 => Error::last_result(),
+            _ => Ok(Token(unsafe { Handle::new(handle) })),
+        }
+    }
+    /// Enables a privilege such as `"SeShutdownPrivilege"` on the token, which must have been
+    /// opened with `TOKEN_ADJUST_PRIVILEGES`.
+    /// Fails with `ERROR_NOT_ALL_ASSIGNED` if the token does not hold the privilege, which
+    /// `AdjustTokenPrivileges` itself reports as success.
+    pub fn enable_privilege(&self, name: &str) -> Result<()> {
+        let mut privileges = TOKEN_PRIVILEGES {
+            PrivilegeCount: 1,
+            Privileges: [LUID_AND_ATTRIBUTES {
+                Luid: unsafe { zeroed() },
+                Attributes: SE_PRIVILEGE_ENABLED,
+            }],
+        };
+        let res = unsafe {
+            LookupPrivilegeValueW(
+                null(),
+                name.to_wide_null().as_ptr(),
+                &mut privileges.Privileges[0].Luid,
+            )
+        };
+        if res == 0 {
+            return Error::last_result();
+        }
+        let res = unsafe {
+            AdjustTokenPrivileges(*self.0, FALSE, &mut privileges, 0, null_mut(), null_mut())
+        };
+        // The last error is set even on success, to distinguish a partial success
+        let err = Error::last();
+        if res == 0 || err.code() == ERROR_NOT_ALL_ASSIGNED {
+            return Err(err);
+        }
+        Ok(())
+    }
+    /// The user the token belongs to. The token must have been opened with `TOKEN_QUERY`, as for
+    /// all of the queries.
+    pub fn user_sid(&self) -> Result<Sid> {
+        let info = self.information::<TOKEN_USER>(TokenUser)?;
+        unsafe { Sid::copy_from(info.as_ref().User.Sid) }
+    }
+    /// Whether the token is elevated, such as when running as administrator with UAC enabled.
+    pub fn is_elevated(&self) -> Result<bool> {
+        let info = self.information::<TOKEN_ELEVATION>(TokenElevation)?;
+        Ok(unsafe { info.as_ref().TokenIsElevated } != 0)
+    }
+    /// The mandatory integrity level of the token.
+    pub fn integrity_level(&self) -> Result<IntegrityLevel> {
+        let info = self.information::<TOKEN_MANDATORY_LABEL>(TokenIntegrityLevel)?;
+        unsafe {
+            // The level is the last subauthority of the label SID
+            let sid = info.as_ref().Label.Sid;
+            if IsValidSid(sid) == 0 {
+                return Err(Error::new(ERROR_INVALID_SID));
+            }
+            let count = *GetSidSubAuthorityCount(sid);
+            if count == 0 {
+                return Err(Error::new(ERROR_INVALID_SID));
+            }
+            let rid = *GetSidSubAuthority(sid, DWORD::from(count) - 1);
+            Ok(IntegrityLevel::from_raw(rid))
+        }
+    }
+    /// Queries information whose size is not known up front, such as the structs which point
+    /// into trailing data after them.
+    fn information<T>(&self, class: TOKEN_INFORMATION_CLASS) -> Result<VariableSizedBox<T>> {
+        let mut len = 0;
+        let res = unsafe { GetTokenInformation(*self.0, class, null_mut(), 0, &mut len) };
+        if res == 0 {
+            let err = Error::last();
+            if err.code() != ERROR_INSUFFICIENT_BUFFER {
+                return Err(err);
+            }
+        }
+        let mut info = VariableSizedBox::<T>::new(len as usize);
+        let res =
+            unsafe { GetTokenInformation(*self.0, class, info.as_mut_ptr().cast(), len, &mut len) };
+        if res == 0 {
+            return Error::last_result();
+        }
+        Ok(info)
+    }
+    pub fn try_clone(&self) -> Result<Token> {
+        unsafe { Handle::duplicate_from(*self.0).map(Token) }
+    }
+}
+impl AsRawHandle for Token {
+    fn as_raw_handle(&self) -> HANDLE {
+        *self.0
+    }
+}
+unsafe impl Send for Token {}
+unsafe impl Sync for Token {}
+
+/// The mandatory integrity level of a token
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IntegrityLevel {
+    Untrusted,
+    Low,
+    Medium,
+    MediumPlus,
+    High,
+    System,
+    /// Any other `SECURITY_MANDATORY_*_RID`, such as for protected processes
+    Other(u32),
+}
+impl IntegrityLevel {
+    fn from_raw(rid: DWORD) -> IntegrityLevel {
+        match rid {
+            SECURITY_MANDATORY_UNTRUSTED_RID => IntegrityLevel::Untrusted,
+            SECURITY_MANDATORY_LOW_RID => IntegrityLevel::Low,
+            SECURITY_MANDATORY_MEDIUM_RID => IntegrityLevel::Medium,
+            SECURITY_MANDATORY_MEDIUM_PLUS_RID => IntegrityLevel::MediumPlus,
+            SECURITY_MANDATORY_HIGH_RID => IntegrityLevel::High,
+            SECURITY_MANDATORY_SYSTEM_RID => IntegrityLevel::System,
+            other => IntegrityLevel::Other(other),
+        }
+    }
+}
+
+/// Impersonates the user of `token` on the current thread until the guard is dropped.
+/// The token must have been opened with `TOKEN_QUERY` and `TOKEN_DUPLICATE`.
+pub fn impersonate(token: &Token) -> Result<ImpersonationGuard> {
+    match unsafe { ImpersonateLoggedOnUser(*token.0) } {
+        0 => Error::last_result(),
+        _ => Ok(ImpersonationGuard(PhantomData)),
+    }
+}
+
+/// Reverts the current thread to its own security context on drop.
+/// Impersonation belongs to the thread which started it, so the guard cannot be sent to another
+/// thread.
+#[derive(Debug)]
+pub struct ImpersonationGuard(PhantomData<*mut ()>);
+impl Drop for ImpersonationGuard {
+    fn drop(&mut self) {
+        unsafe { RevertToSelf() };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sid;
+
+    #[test]
+    fn sid_bytes_round_trip() {
+        let s = Sid::admin_group().unwrap();
+        assert_eq!(s.to_string().unwrap(), "S-1-5-32-544");
+        assert!(unsafe { Sid::from_bytes(s.as_bytes()) }.unwrap() == s);
+    }
+
+    #[test]
+    fn sid_from_truncated_bytes() {
+        let s = Sid::admin_group().unwrap();
+        let bytes = s.as_bytes();
+        assert!(unsafe { Sid::from_bytes(&bytes[..1]) }.is_err());
+        assert!(unsafe { Sid::from_bytes(&bytes[..bytes.len() - 1]) }.is_err());
+    }
+}
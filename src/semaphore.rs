@@ -0,0 +1,110 @@
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// All files in the project carrying such notice may not be copied, modified, or distributed
+// except according to those terms.
+use error::{Error, Result};
+use handle::Handle;
+use security::SecurityAttributes;
+use std::{mem::forget, os::windows::io::AsRawHandle, time::Duration};
+use wait::timeout_ms;
+use wide::ToWide;
+use winapi::{
+    shared::minwindef::FALSE,
+    um::{
+        synchapi::{CreateSemaphoreW, OpenSemaphoreW, ReleaseSemaphore},
+        winbase::INFINITE,
+        winnt::{HANDLE, SEMAPHORE_MODIFY_STATE, SYNCHRONIZE},
+    },
+};
+
+/// A named semaphore object
+#[derive(Debug)]
+pub struct Semaphore(Handle);
+impl Semaphore {
+    pub fn create(
+        initial: i32,
+        maximum: i32,
+        security_attributes: Option<&SecurityAttributes>,
+        name: &str,
+    ) -> Result<Semaphore> {
+        let mut security_attributes = SecurityAttributes::as_raw_opt(security_attributes);
+        unsafe {
+            let handle = CreateSemaphoreW(
+                SecurityAttributes::raw_opt_ptr(&mut security_attributes),
+                initial,
+                maximum,
+                name.to_wide_null().as_ptr(),
+            );
+            if handle.is_null() {
+                return Error::last_result();
+            }
+            Ok(Semaphore(Handle::new(handle)))
+        }
+    }
+    pub fn open(name: &str) -> Result<Semaphore> {
+        unsafe {
+            let handle = OpenSemaphoreW(
+                SEMAPHORE_MODIFY_STATE | SYNCHRONIZE,
+                FALSE,
+                name.to_wide_null().as_ptr(),
+            );
+            if handle.is_null() {
+                return Error::last_result();
+            }
+            Ok(Semaphore(Handle::new(handle)))
+        }
+    }
+    /// Decrements the count, waiting for it to become nonzero first.
+    /// The count is incremented again when the guard is dropped.
+    /// The timeout is specified in milliseconds
+    /// Specifying None for the timeout means to wait forever
+    /// Returns `None` if the timeout elapsed before the count became nonzero.
+    pub fn acquire(&self, timeout: Option<u32>) -> Result<Option<SemaphoreGuard<'_>>> {
+        let acquired = self.0.wait_ms(timeout.unwrap_or(INFINITE))?;
+        Ok(if acquired {
+            Some(SemaphoreGuard(self))
+        } else {
+            None
+        })
+    }
+    /// Like `acquire`, but with the timeout as a `Duration` which is rounded up to whole
+    /// milliseconds
+    pub fn acquire_timeout(&self, timeout: Option<Duration>) -> Result<Option<SemaphoreGuard<'_>>> {
+        self.acquire(Some(timeout_ms(timeout)))
+    }
+    /// Increments the count, returning the previous count.
+    /// Fails if this would take the count over the maximum.
+    pub fn release(&self, count: i32) -> Result<i32> {
+        let mut previous = 0;
+        match unsafe { ReleaseSemaphore(*self.0, count, &mut previous) } {
+            0 => Error::last_result(),
+            _ => Ok(previous),
+        }
+    }
+    pub fn try_clone(&self) -> Result<Semaphore> {
+        unsafe { Handle::duplicate_from(*self.0).map(Semaphore) }
+    }
+}
+unsafe impl Send for Semaphore {}
+unsafe impl Sync for Semaphore {}
+
+pub struct SemaphoreGuard<'a>(&'a Semaphore);
+impl<'a> SemaphoreGuard<'a> {
+    /// Keeps the count decremented instead of releasing it.
+    pub fn forget(self) {
+        forget(self);
+    }
+}
+impl<'a> Drop for SemaphoreGuard<'a> {
+    fn drop(&mut self) {
+        if let Err(err) = self.0.release(1) {
+            panic!("failed to call ReleaseSemaphore: {}", err);
+        }
+    }
+}
+impl AsRawHandle for Semaphore {
+    fn as_raw_handle(&self) -> HANDLE {
+        *self.0
+    }
+}
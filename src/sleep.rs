@@ -3,21 +3,52 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // All files in the project carrying such notice may not be copied, modified, or distributed
 // except according to those terms.
-use {k32, w};
+use perf::Stopwatch;
+use std::time::Duration;
+use wait::timeout_ms;
+use winapi::{
+    shared::minwindef::TRUE,
+    um::{
+        synchapi::{Sleep, SleepEx},
+        winbase::WAIT_IO_COMPLETION,
+    },
+};
 
-pub fn sleep(ms: u32) {
-    unsafe { k32::Sleep(ms) }
+/// The duration is rounded up to whole milliseconds.
+pub fn sleep(duration: Duration) {
+    sleep_ms(timeout_ms(Some(duration)))
 }
-#[derive(Debug, Eq, PartialEq)]
+pub fn sleep_ms(ms: u32) {
+    unsafe { Sleep(ms) }
+}
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub enum WakeReason {
-    TimedOut,
-    CallbacksFired,
+    TimedOut {
+        elapsed: Duration,
+    },
+    /// The sleep was cut short to run queued APCs or I/O completion callbacks.
+    /// `remaining` is how much longer the sleep would have lasted, for sleeping again.
+    CallbacksFired {
+        elapsed: Duration,
+        remaining: Duration,
+    },
+}
+/// Sleeps until the duration elapses or callbacks are run on this thread.
+/// The duration is rounded up to whole milliseconds.
+pub fn sleep_alertable(duration: Duration) -> WakeReason {
+    sleep_alertable_ms(timeout_ms(Some(duration)))
 }
-pub fn sleep_alertable(ms: u32) -> WakeReason {
-    let ret = unsafe { k32::SleepEx(ms, w::TRUE) };
+/// Like `sleep_alertable` with the duration in milliseconds.
+pub fn sleep_alertable_ms(ms: u32) -> WakeReason {
+    let stopwatch = Stopwatch::start();
+    let ret = unsafe { SleepEx(ms, TRUE) };
+    let elapsed = stopwatch.elapsed();
     match ret {
-        0 => WakeReason::TimedOut,
-        w::WAIT_IO_COMPLETION => WakeReason::CallbacksFired,
+        0 => WakeReason::TimedOut { elapsed },
+        WAIT_IO_COMPLETION => WakeReason::CallbacksFired {
+            elapsed,
+            remaining: Duration::from_millis(ms.into()).saturating_sub(elapsed),
+        },
         _ => unreachable!("SleepEx returned weird value of {:?}", ret),
     }
 }
@@ -3,24 +3,63 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // All files in the project carrying such notice may not be copied, modified, or distributed
 // except according to those terms.
-use {Result, k32, last_error, w};
-use handle::{Handle};
-use std::os::windows::io::{AsRawHandle, FromRawHandle, IntoRawHandle};
-use std::thread::{JoinHandle};
+use error::{Error, Result};
+use handle::Handle;
+use std::{
+    os::windows::io::{AsRawHandle, FromRawHandle, IntoRawHandle},
+    thread::JoinHandle,
+};
+use winapi::{
+    shared::minwindef::DWORD,
+    um::{
+        processthreadsapi::{
+            GetCurrentThread, GetThreadId, GetThreadPriority, SetThreadIdealProcessor,
+            SetThreadPriority,
+        },
+        winbase::{SetThreadAffinityMask, THREAD_PRIORITY_ERROR_RETURN},
+        winnt::HANDLE,
+    },
+};
 
 pub struct Thread(Handle);
 impl Thread {
     pub fn current() -> Result<Thread> {
-        unsafe { Handle::duplicate_from(k32::GetCurrentThread()).map(Thread) }
+        unsafe { Handle::duplicate_from(GetCurrentThread()).map(Thread) }
     }
     /// Returns the old affinity mask on success
     pub fn set_affinity_mask(&self, mask: usize) -> Result<usize> {
-        let res = unsafe {
-            k32::SetThreadAffinityMask(*self.0, mask as w::ULONG_PTR)
-        };
+        let res = unsafe { SetThreadAffinityMask(*self.0, mask) };
         match res {
-            0 => last_error(),
-            prev => Ok(prev as usize),
+            0 => Error::last_result(),
+            prev => Ok(prev),
+        }
+    }
+    /// The system wide identifier of the thread
+    pub fn id(&self) -> Result<u32> {
+        match unsafe { GetThreadId(*self.0) } {
+            0 => Error::last_result(),
+            id => Ok(id),
+        }
+    }
+    pub fn priority(&self) -> Result<ThreadPriority> {
+        let res = unsafe { GetThreadPriority(*self.0) };
+        if res == THREAD_PRIORITY_ERROR_RETURN as i32 {
+            return Error::last_result();
+        }
+        Ok(ThreadPriority::from_raw(res))
+    }
+    pub fn set_priority(&self, priority: ThreadPriority) -> Result<()> {
+        match unsafe { SetThreadPriority(*self.0, priority.to_raw()) } {
+            0 => Error::last_result(),
+            _ => Ok(()),
+        }
+    }
+    /// Hints which processor the thread should preferably run on.
+    /// Returns the previous ideal processor on success
+    pub fn set_ideal_processor(&self, processor: u32) -> Result<u32> {
+        match unsafe { SetThreadIdealProcessor(*self.0, processor) } {
+            DWORD::MAX => Error::last_result(),
+            prev => Ok(prev),
         }
     }
 }
@@ -30,22 +69,67 @@ impl<T> From<JoinHandle<T>> for Thread {
     }
 }
 impl<'a, T> From<&'a JoinHandle<T>> for Thread {
+    /// The handle is duplicated since the `JoinHandle` still owns its own.
+    /// # Panics
+    /// Panics if the handle cannot be duplicated.
     fn from(o: &'a JoinHandle<T>) -> Thread {
-        unsafe { Thread::from_raw_handle(o.as_raw_handle()) }
+        unsafe { Handle::duplicate_from(o.as_raw_handle()).map(Thread) }
+            .expect("failed to duplicate thread handle")
     }
 }
 impl AsRawHandle for Thread {
-    fn as_raw_handle(&self) -> w::HANDLE {
+    fn as_raw_handle(&self) -> HANDLE {
         self.0.as_raw_handle()
     }
 }
 impl IntoRawHandle for Thread {
-    fn into_raw_handle(self) -> w::HANDLE {
+    fn into_raw_handle(self) -> HANDLE {
         self.0.into_raw_handle()
     }
 }
 impl FromRawHandle for Thread {
-    unsafe fn from_raw_handle(handle: w::HANDLE) -> Thread {
+    unsafe fn from_raw_handle(handle: HANDLE) -> Thread {
         Thread(Handle::from_raw_handle(handle))
     }
 }
+
+/// The priority of a thread relative to the priority class of its process
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ThreadPriority {
+    Idle,
+    Lowest,
+    BelowNormal,
+    Normal,
+    AboveNormal,
+    Highest,
+    TimeCritical,
+    /// Processes in the realtime priority class can use any level from -7 to 6.
+    Other(i32),
+}
+impl ThreadPriority {
+    fn from_raw(priority: i32) -> ThreadPriority {
+        match priority {
+            -15 => ThreadPriority::Idle,
+            -2 => ThreadPriority::Lowest,
+            -1 => ThreadPriority::BelowNormal,
+            0 => ThreadPriority::Normal,
+            1 => ThreadPriority::AboveNormal,
+            2 => ThreadPriority::Highest,
+            15 => ThreadPriority::TimeCritical,
+            other => ThreadPriority::Other(other),
+        }
+    }
+    fn to_raw(self) -> i32 {
+        // winapi defines the THREAD_PRIORITY constants as unsigned, so these are spelled out
+        match self {
+            ThreadPriority::Idle => -15,
+            ThreadPriority::Lowest => -2,
+            ThreadPriority::BelowNormal => -1,
+            ThreadPriority::Normal => 0,
+            ThreadPriority::AboveNormal => 1,
+            ThreadPriority::Highest => 2,
+            ThreadPriority::TimeCritical => 15,
+            ThreadPriority::Other(other) => other,
+        }
+    }
+}
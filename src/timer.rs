@@ -0,0 +1,120 @@
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// All files in the project carrying such notice may not be copied, modified, or distributed
+// except according to those terms.
+use error::{Error, Result};
+pub use event::WaitState;
+use handle::Handle;
+use security::SecurityAttributes;
+use std::{
+    mem::zeroed,
+    os::windows::io::AsRawHandle,
+    ptr::{null, null_mut},
+    time::Duration,
+};
+use wait::timeout_ms;
+use wide::ToWide;
+use winapi::{
+    shared::{
+        minwindef::{BOOL, FALSE},
+        ntdef::LONG,
+        winerror::WAIT_TIMEOUT,
+    },
+    um::{
+        synchapi::{
+            CancelWaitableTimer, CreateWaitableTimerW, SetWaitableTimer, WaitForSingleObject,
+        },
+        winbase::WAIT_OBJECT_0,
+        winnt::{HANDLE, LARGE_INTEGER},
+    },
+};
+
+/// A kernel waitable timer, which becomes signaled when its due time arrives
+#[derive(Debug)]
+pub struct WaitableTimer(Handle);
+impl WaitableTimer {
+    /// A manual reset timer stays signaled until it is set again, releasing every waiting
+    /// thread. A synchronization timer is reset as soon as a single waiting thread is released.
+    /// The timer is created inactive.
+    pub fn create(
+        manual_reset: bool,
+        security_attributes: Option<&SecurityAttributes>,
+        name: Option<&str>,
+    ) -> Result<WaitableTimer> {
+        let mut security_attributes = SecurityAttributes::as_raw_opt(security_attributes);
+        let name = name.map(|name| name.to_wide_null());
+        unsafe {
+            let handle = CreateWaitableTimerW(
+                SecurityAttributes::raw_opt_ptr(&mut security_attributes),
+                manual_reset as BOOL,
+                name.as_ref().map(|name| name.as_ptr()).unwrap_or(null()),
+            );
+            if handle.is_null() {
+                return Error::last_result();
+            }
+            Ok(WaitableTimer(Handle::new(handle)))
+        }
+    }
+    /// Activates the timer so it is signaled once `due` has elapsed from now, and then every
+    /// `period` after that if one is given. Setting an active timer replaces its due time.
+    /// The period is rounded up to whole milliseconds.
+    pub fn set(&self, due: Duration, period: Option<Duration>) -> Result<()> {
+        let mut due_time: LARGE_INTEGER = unsafe { zeroed() };
+        unsafe {
+            *due_time.QuadPart_mut() = relative_due_time(due);
+        }
+        let period = match period {
+            Some(period) => timeout_ms(Some(period)).min(LONG::MAX as u32) as LONG,
+            None => 0,
+        };
+        let res = unsafe { SetWaitableTimer(*self.0, &due_time, period, None, null_mut(), FALSE) };
+        match res {
+            0 => Error::last_result(),
+            _ => Ok(()),
+        }
+    }
+    /// Deactivates the timer without changing whether it is currently signaled.
+    pub fn cancel(&self) -> Result<()> {
+        match unsafe { CancelWaitableTimer(*self.0) } {
+            0 => Error::last_result(),
+            _ => Ok(()),
+        }
+    }
+    /// Specifying None for the timeout means to wait forever.
+    /// The timeout is rounded up to whole milliseconds.
+    pub fn wait(&self, timeout: Option<Duration>) -> Result<WaitState> {
+        match unsafe { WaitForSingleObject(*self.0, timeout_ms(timeout)) } {
+            WAIT_OBJECT_0 => Ok(WaitState::Signaled),
+            WAIT_TIMEOUT => Ok(WaitState::TimedOut),
+            _ => Error::last_result(),
+        }
+    }
+    pub fn try_clone(&self) -> Result<WaitableTimer> {
+        unsafe { Handle::duplicate_from(*self.0).map(WaitableTimer) }
+    }
+}
+unsafe impl Send for WaitableTimer {}
+unsafe impl Sync for WaitableTimer {}
+impl AsRawHandle for WaitableTimer {
+    fn as_raw_handle(&self) -> HANDLE {
+        *self.0
+    }
+}
+
+/// Due times are in 100 nanosecond intervals, where negative values are relative to now and
+/// positive values are absolute `FILETIME`s. The interval is rounded up so the timer never
+/// fires early, and a zero duration becomes the smallest relative time rather than 0, which
+/// would be an absolute time.
+// u32::div_ceil would need Rust 1.73
+#[allow(clippy::manual_div_ceil)]
+fn relative_due_time(due: Duration) -> i64 {
+    let intervals = due
+        .as_secs()
+        .checked_mul(10_000_000)
+        .and_then(|t| t.checked_add(u64::from((due.subsec_nanos() + 99) / 100)))
+        .unwrap_or(i64::MAX as u64)
+        .min(i64::MAX as u64)
+        .max(1);
+    -(intervals as i64)
+}
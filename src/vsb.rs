@@ -5,8 +5,9 @@
 // except according to those terms.
 use std::{
     alloc::{alloc_zeroed, dealloc, handle_alloc_error, realloc, Layout},
+    fmt::{Debug, Formatter, Result as FmtResult},
     marker::PhantomData,
-    mem::{align_of, size_of},
+    mem::{align_of, size_of, size_of_val},
     ptr::{self, NonNull},
     slice::{from_raw_parts, from_raw_parts_mut},
 };
@@ -15,19 +16,36 @@ use std::{
 /// by either another field, or an external source of information.
 pub struct VariableSizedBox<T> {
     size: usize,
+    align: usize,
     data: NonNull<T>,
     pd: PhantomData<T>,
 }
 impl<T> VariableSizedBox<T> {
     /// The size is specified in bytes. The data is zeroed.
     pub fn new(size: usize) -> VariableSizedBox<T> {
+        VariableSizedBox::new_with_align(size, align_of::<T>())
+    }
+    /// Like `new`, but with a larger alignment than that of `T`, which is kept for the lifetime
+    /// of the box including across resizes.
+    /// # Panics
+    /// Panics if `align` is not a power of two or is less than the alignment of `T`.
+    pub fn new_with_align(size: usize, align: usize) -> VariableSizedBox<T> {
+        assert!(align.is_power_of_two(), "alignment must be a power of two");
+        assert!(
+            align >= align_of::<T>(),
+            "alignment must be at least that of T"
+        );
         if size == 0 {
-            return VariableSizedBox::default();
+            return VariableSizedBox {
+                align,
+                ..VariableSizedBox::default()
+            };
         }
-        let layout = Layout::from_size_align(size, align_of::<T>()).unwrap();
+        let layout = Layout::from_size_align(size, align).unwrap();
         if let Some(data) = NonNull::new(unsafe { alloc_zeroed(layout) }) {
             VariableSizedBox {
                 size,
+                align,
                 data: data.cast(),
                 pd: PhantomData,
             }
@@ -35,6 +53,27 @@ impl<T> VariableSizedBox<T> {
             handle_alloc_error(layout)
         }
     }
+    /// Allocates a copy of the bytes, such as a blob previously obtained from `as_bytes`.
+    /// The length does not need to be a multiple of the alignment of `T`.
+    pub fn from_bytes(bytes: &[u8]) -> VariableSizedBox<T> {
+        let mut this = VariableSizedBox::new(bytes.len());
+        this.as_bytes_mut().copy_from_slice(bytes);
+        this
+    }
+    /// Allocates a copy of the elements, using the size of the whole slice in bytes.
+    pub fn from_slice_of<U: Copy>(slice: &[U]) -> VariableSizedBox<T> {
+        let size = size_of_val(slice);
+        let this = VariableSizedBox::<T>::new(size);
+        if size != 0 {
+            unsafe {
+                slice
+                    .as_ptr()
+                    .cast::<u8>()
+                    .copy_to_nonoverlapping(this.data.as_ptr().cast::<u8>(), size);
+            }
+        }
+        this
+    }
     /// Use this to get a pointer to pass to FFI functions.
     pub fn as_ptr(&self) -> *const T {
         if self.size == 0 {
@@ -69,9 +108,9 @@ impl<T> VariableSizedBox<T> {
     /// If this grows the allocation, the extra bytes will be zeroed.
     pub fn resize(&mut self, size: usize) {
         if size == 0 || self.size == 0 {
-            *self = VariableSizedBox::new(size);
+            *self = VariableSizedBox::new_with_align(size, self.align);
         } else if size > self.size {
-            let new = VariableSizedBox::<T>::new(size);
+            let new = VariableSizedBox::<T>::new_with_align(size, self.align);
             unsafe {
                 self.data
                     .as_ptr()
@@ -80,21 +119,50 @@ impl<T> VariableSizedBox<T> {
             }
             *self = new;
         } else if size < self.size {
-            let layout = Layout::from_size_align(size, align_of::<T>()).unwrap();
+            // realloc takes the layout the memory is currently allocated with
+            let layout = Layout::from_size_align(self.size, self.align).unwrap();
             if let Some(data) =
                 NonNull::new(unsafe { realloc(self.as_mut_ptr().cast(), layout, size) })
             {
                 self.data = data.cast();
                 self.size = size;
             } else {
-                handle_alloc_error(layout)
+                handle_alloc_error(Layout::from_size_align(size, self.align).unwrap())
             }
         }
     }
+    /// The size is specified in bytes.
+    /// Unlike `resize`, growing the allocation discards the existing contents instead of copying
+    /// them, leaving the entire allocation zeroed. Use this when the contents are about to be
+    /// overwritten anyway, such as when retrying a call with the size it asked for.
+    pub fn reserve(&mut self, size: usize) {
+        if size > self.size {
+            // Free the old allocation first so the allocator can reuse it
+            let align = self.align;
+            *self = VariableSizedBox::default();
+            *self = VariableSizedBox::new_with_align(size, align);
+        } else {
+            self.resize(size);
+        }
+    }
     /// The length of the allocation specified in bytes.
     pub fn len(&self) -> usize {
         self.size
     }
+    /// The entire allocation as bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        if self.size == 0 {
+            return &[];
+        }
+        unsafe { from_raw_parts(self.data.as_ptr().cast(), self.size) }
+    }
+    /// The entire allocation as mutable bytes.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        if self.size == 0 {
+            return &mut [];
+        }
+        unsafe { from_raw_parts_mut(self.data.as_ptr().cast(), self.size) }
+    }
     /// Given a pointer to a specific field, upgrades the provenance of the pointer to the entire
     /// allocation to work around stacked borrows.
     /// # Safety
@@ -163,6 +231,28 @@ impl<T> VariableSizedBox<T> {
     pub unsafe fn slice_from_count_mut<U>(&mut self, ptr: *mut U, count: usize) -> &mut [U] {
         self.try_slice_from_count_mut(ptr, count).unwrap()
     }
+    /// Returns a slice to a variable sized array of `count` elements starting `header_len` bytes
+    /// into the allocation, such as a trailing array after a fixed size header.
+    /// Will panic if the slice is not entirely within the allocation.
+    /// # Safety
+    /// The data must be valid for the specified type and the array must be suitably aligned.
+    pub unsafe fn slice_of<U>(&self, header_len: usize, count: usize) -> &[U] {
+        let ptr = self.as_ptr().cast::<u8>().wrapping_add(header_len).cast();
+        self.slice_from_count(ptr, count)
+    }
+    /// Returns a mutable slice to a variable sized array of `count` elements starting
+    /// `header_len` bytes into the allocation, such as a trailing array after a fixed size header.
+    /// Will panic if the slice is not entirely within the allocation.
+    /// # Safety
+    /// The data must be valid for the specified type and the array must be suitably aligned.
+    pub unsafe fn slice_of_mut<U>(&mut self, header_len: usize, count: usize) -> &mut [U] {
+        let ptr = self
+            .as_mut_ptr()
+            .cast::<u8>()
+            .wrapping_add(header_len)
+            .cast();
+        self.slice_from_count_mut(ptr, count)
+    }
     /// Given a pointer to a variable sized array field and the length of the array in bytes,
     /// returns a slice to the entire variable sized array.
     /// Will return `None` if the slice is not entirely within the allocation.
@@ -213,7 +303,7 @@ impl<T> VariableSizedBox<T> {
         ptr: *const U,
         total_bytes: usize,
     ) -> Option<&[U]> {
-        let bytes = total_bytes - (ptr as usize - self.as_ptr() as usize);
+        let bytes = self.bytes_after(ptr.cast(), total_bytes)?;
         self.try_slice_from_bytes(ptr, bytes)
     }
     /// Given a pointer to a variable sized array field and the size of the entire struct in bytes
@@ -222,7 +312,9 @@ impl<T> VariableSizedBox<T> {
     /// # Safety
     /// The data must be valid for the specified type.
     pub unsafe fn slice_from_total_bytes<U>(&self, ptr: *const U, total_bytes: usize) -> &[U] {
-        let bytes = total_bytes - (ptr as usize - self.as_ptr() as usize);
+        let bytes = self
+            .bytes_after(ptr.cast(), total_bytes)
+            .expect("total_bytes is smaller than the offset of the field");
         self.slice_from_bytes(ptr, bytes)
     }
     /// Given a pointer to a variable sized array field and the size of the entire struct in bytes
@@ -236,7 +328,7 @@ impl<T> VariableSizedBox<T> {
         ptr: *mut U,
         total_bytes: usize,
     ) -> Option<&mut [U]> {
-        let bytes = total_bytes - (ptr as usize - self.as_ptr() as usize);
+        let bytes = self.bytes_after(ptr.cast(), total_bytes)?;
         self.try_slice_from_bytes_mut(ptr, bytes)
     }
     /// Given a pointer to a variable sized array field and the size of the entire struct in bytes
@@ -250,25 +342,91 @@ impl<T> VariableSizedBox<T> {
         ptr: *mut U,
         total_bytes: usize,
     ) -> &mut [U] {
-        let bytes = total_bytes - (ptr as usize - self.as_ptr() as usize);
+        let bytes = self
+            .bytes_after(ptr.cast(), total_bytes)
+            .expect("total_bytes is smaller than the offset of the field");
         self.slice_from_bytes_mut(ptr, bytes)
     }
+    // The number of bytes from `ptr` to the end of a struct `total_bytes` long, or `None` if
+    // `ptr` is before the start of the allocation or beyond `total_bytes`.
+    fn bytes_after(&self, ptr: *const u8, total_bytes: usize) -> Option<usize> {
+        let offset = (ptr as usize).checked_sub(self.as_ptr() as usize)?;
+        total_bytes.checked_sub(offset)
+    }
 }
 impl<T> Drop for VariableSizedBox<T> {
     fn drop(&mut self) {
         if self.size == 0 {
             return;
         }
-        let layout = Layout::from_size_align(self.size, align_of::<T>()).unwrap();
+        let layout = Layout::from_size_align(self.size, self.align).unwrap();
         unsafe { dealloc(self.as_mut_ptr().cast(), layout) }
     }
 }
+impl<T> Debug for VariableSizedBox<T> {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        // Shows the leading bytes in hex
+        struct Preview<'a>(&'a [u8]);
+        impl<'a> Debug for Preview<'a> {
+            fn fmt(&self, f: &mut Formatter) -> FmtResult {
+                const MAX: usize = 32;
+                for (i, byte) in self.0.iter().take(MAX).enumerate() {
+                    if i != 0 {
+                        f.write_str(" ")?;
+                    }
+                    write!(f, "{:02x}", byte)?;
+                }
+                if self.0.len() > MAX {
+                    f.write_str(" ...")?;
+                }
+                Ok(())
+            }
+        }
+        let mut s = f.debug_struct("VariableSizedBox");
+        s.field("size", &self.size);
+        if self.size != 0 {
+            s.field("data", &Preview(self.as_bytes()));
+        }
+        s.finish()
+    }
+}
 impl<T> Default for VariableSizedBox<T> {
     fn default() -> Self {
         VariableSizedBox {
             size: 0,
+            align: align_of::<T>(),
             data: NonNull::dangling(),
             pd: PhantomData,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::VariableSizedBox;
+
+    #[repr(C)]
+    struct Header {
+        len: u32,
+        data: [u16; 1],
+    }
+
+    #[test]
+    fn total_bytes_before_field() {
+        let mut vsb = VariableSizedBox::<Header>::new(16);
+        unsafe {
+            let ptr = &(*vsb.as_ptr()).data as *const u16;
+            assert!(vsb.try_slice_from_total_bytes(ptr, 2).is_none());
+            assert_eq!(
+                vsb.try_slice_from_total_bytes(ptr, 12).map(|s| s.len()),
+                Some(4)
+            );
+            let ptr = &mut (*vsb.as_mut_ptr()).data as *mut u16;
+            assert!(vsb.try_slice_from_total_bytes_mut(ptr, 2).is_none());
+            assert_eq!(
+                vsb.try_slice_from_total_bytes_mut(ptr, 12).map(|s| s.len()),
+                Some(4)
+            );
+        }
+    }
+}
@@ -0,0 +1,88 @@
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// All files in the project carrying such notice may not be copied, modified, or distributed
+// except according to those terms.
+use error::{Error, Result};
+use std::{os::windows::io::AsRawHandle, time::Duration};
+use winapi::{
+    shared::{
+        minwindef::{BOOL, DWORD, FALSE, TRUE},
+        winerror::{ERROR_INVALID_PARAMETER, WAIT_TIMEOUT},
+    },
+    um::{
+        synchapi::WaitForMultipleObjects,
+        winbase::{INFINITE, WAIT_ABANDONED_0, WAIT_OBJECT_0},
+        winnt::{HANDLE, MAXIMUM_WAIT_OBJECTS},
+    },
+};
+
+/// The outcome of waiting on several objects at once
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WaitOutcome {
+    /// The object at this index was signaled.
+    /// For `wait_all` every object was signaled and the index carries no meaning.
+    Signaled(usize),
+    /// The object at this index is a mutex which was abandoned by the thread that owned it.
+    Abandoned(usize),
+    TimedOut,
+}
+
+/// Waits until any one of the objects is signaled, reporting the lowest such index.
+/// The timeout is specified in milliseconds
+/// Specifying None for the timeout means to wait forever
+/// At most `MAXIMUM_WAIT_OBJECTS` can be waited on, otherwise this fails with
+/// `ERROR_INVALID_PARAMETER`.
+pub fn wait_any(handles: &[&dyn AsRawHandle], timeout: Option<u32>) -> Result<WaitOutcome> {
+    wait_multiple(handles, FALSE, timeout)
+}
+/// Waits until all of the objects are signaled at the same time.
+/// The timeout is specified in milliseconds
+/// Specifying None for the timeout means to wait forever
+/// At most `MAXIMUM_WAIT_OBJECTS` can be waited on, otherwise this fails with
+/// `ERROR_INVALID_PARAMETER`.
+pub fn wait_all(handles: &[&dyn AsRawHandle], timeout: Option<u32>) -> Result<WaitOutcome> {
+    wait_multiple(handles, TRUE, timeout)
+}
+fn wait_multiple(
+    handles: &[&dyn AsRawHandle],
+    wait_all: BOOL,
+    timeout: Option<u32>,
+) -> Result<WaitOutcome> {
+    if handles.is_empty() || handles.len() > MAXIMUM_WAIT_OBJECTS as usize {
+        return Err(Error::new(ERROR_INVALID_PARAMETER));
+    }
+    let raw: Vec<HANDLE> = handles.iter().map(|h| h.as_raw_handle()).collect();
+    let len = raw.len() as DWORD;
+    let res =
+        unsafe { WaitForMultipleObjects(len, raw.as_ptr(), wait_all, timeout.unwrap_or(INFINITE)) };
+    match res {
+        WAIT_TIMEOUT => Ok(WaitOutcome::TimedOut),
+        x if x.wrapping_sub(WAIT_OBJECT_0) < len => {
+            Ok(WaitOutcome::Signaled(x.wrapping_sub(WAIT_OBJECT_0) as usize))
+        }
+        x if x.wrapping_sub(WAIT_ABANDONED_0) < len => Ok(WaitOutcome::Abandoned(
+            x.wrapping_sub(WAIT_ABANDONED_0) as usize,
+        )),
+        _ => Error::last_result(),
+    }
+}
+/// Converts a timeout into milliseconds for the wait functions, where `None` means `INFINITE`.
+/// Durations are rounded up to the next millisecond so a tiny timeout never becomes a poll, and
+/// durations too long to represent are clamped to just under `INFINITE`.
+// u32::div_ceil would need Rust 1.73
+#[allow(clippy::manual_div_ceil)]
+pub(crate) fn timeout_ms(timeout: Option<Duration>) -> DWORD {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return INFINITE,
+    };
+    let ms = timeout
+        .as_secs()
+        .checked_mul(1000)
+        .and_then(|ms| ms.checked_add(u64::from((timeout.subsec_nanos() + 999_999) / 1_000_000)));
+    match ms {
+        Some(ms) if ms < u64::from(INFINITE) => ms as DWORD,
+        _ => INFINITE - 1,
+    }
+}
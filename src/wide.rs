@@ -3,14 +3,49 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // All files in the project carrying such notice may not be copied, modified, or distributed
 // except according to those terms.
+use error::Error as WinError;
+use std::cmp::Ordering;
+use std::error::Error;
 use std::ffi::{OsStr, OsString};
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::ops::Deref;
 use std::os::windows::ffi::{OsStrExt, OsStringExt};
 use std::path::PathBuf;
 use std::slice::from_raw_parts;
+use winapi::shared::minwindef::{BOOL, FALSE, TRUE};
+use winapi::um::stringapiset::CompareStringOrdinal;
+
+// Not provided by winapi
+const CSTR_LESS_THAN: i32 = 1;
+const CSTR_EQUAL: i32 = 2;
+const CSTR_GREATER_THAN: i32 = 3;
 
 pub trait ToWide {
     fn to_wide(&self) -> Vec<u16>;
     fn to_wide_null(&self) -> Vec<u16>;
+    /// Like `to_wide` but reuses the allocation of an existing buffer, which is cleared first.
+    fn to_wide_into(&self, buf: &mut Vec<u16>) {
+        buf.clear();
+        buf.extend(self.to_wide());
+    }
+    /// Like `to_wide_null` but reuses the allocation of an existing buffer, which is cleared
+    /// first.
+    fn to_wide_null_into(&self, buf: &mut Vec<u16>) {
+        buf.clear();
+        buf.extend(self.to_wide_null());
+    }
+    /// Like `to_wide_null` but fails if the string contains an interior nul, which would
+    /// otherwise silently truncate the string when passed to Windows API.
+    fn try_to_wide_null(&self) -> Result<Vec<u16>, NulError> {
+        let mut wide = self.to_wide();
+        match wide.iter().position(|&c| c == 0) {
+            Some(pos) => Err(NulError(pos, wide)),
+            None => {
+                wide.push(0);
+                Ok(wide)
+            }
+        }
+    }
 }
 impl<T> ToWide for T
 where
@@ -24,7 +59,37 @@ where
     fn to_wide_null(&self) -> Vec<u16> {
         self.as_ref().encode_wide().chain(Some(0)).collect()
     }
+    #[inline]
+    fn to_wide_into(&self, buf: &mut Vec<u16>) {
+        buf.clear();
+        buf.extend(self.as_ref().encode_wide());
+    }
+    #[inline]
+    fn to_wide_null_into(&self, buf: &mut Vec<u16>) {
+        buf.clear();
+        buf.extend(self.as_ref().encode_wide().chain(Some(0)));
+    }
 }
+/// An error indicating that an interior nul was found when converting to a nul terminated wide
+/// string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NulError(usize, Vec<u16>);
+impl NulError {
+    /// The position of the nul in the wide string.
+    pub fn nul_position(&self) -> usize {
+        self.0
+    }
+    /// The wide string which contained the nul, without a nul terminator appended.
+    pub fn into_vec(self) -> Vec<u16> {
+        self.1
+    }
+}
+impl Display for NulError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        write!(f, "nul found in wide string at position {}", self.0)
+    }
+}
+impl Error for NulError {}
 pub trait FromWide
 where
     Self: Sized,
@@ -32,8 +97,7 @@ where
     fn from_wide(wide: &[u16]) -> Self;
     #[inline]
     fn from_wide_null(wide: &[u16]) -> Self {
-        let len = wide.iter().take_while(|&&c| c != 0).count();
-        Self::from_wide(&wide[..len])
+        Self::from_wide(&wide[..wide_len(wide)])
     }
     #[inline]
     unsafe fn from_wide_ptr(wide: *const u16, len: usize) -> Self {
@@ -63,3 +127,241 @@ impl FromWide for PathBuf {
         <OsString as OsStringExt>::from_wide(wide).into()
     }
 }
+/// Unpaired surrogates are replaced with U+FFFD, so this is lossy.
+/// Use `OsString` if the original wide string needs to be preserved.
+impl FromWide for String {
+    #[inline]
+    fn from_wide(wide: &[u16]) -> String {
+        String::from_utf16_lossy(wide)
+    }
+}
+/// Converts a nul terminated wide string, such as one returned by Windows API, whose length is
+/// not known.
+/// # Safety
+/// `ptr` must be non-null and point to a valid nul terminated wide string.
+#[inline]
+pub unsafe fn from_wide_ptr<T: FromWide>(ptr: *const u16) -> T {
+    T::from_wide_ptr_null(ptr)
+}
+/// Converts a nul terminated wide string, reading no more than `max_len` elements.
+/// If no nul is found within `max_len` elements, the first `max_len` elements are converted.
+/// # Safety
+/// `ptr` must be non-null and valid for reads up to the nul terminator or `max_len` elements,
+/// whichever comes first.
+#[inline]
+pub unsafe fn from_wide_ptr_bounded<T: FromWide>(ptr: *const u16, max_len: usize) -> T {
+    assert!(!ptr.is_null());
+    let len = (0..max_len).take_while(|&i| *ptr.add(i) != 0).count();
+    T::from_wide_ptr(ptr, len)
+}
+/// A borrowed nul terminated wide string, the UTF-16 counterpart to `CStr`.
+/// It always ends with a nul terminator and contains no interior nuls.
+#[repr(transparent)]
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct WideCStr([u16]);
+impl WideCStr {
+    /// Returns `None` if the slice does not end with a nul or contains an interior nul.
+    pub fn from_slice_with_nul(wide: &[u16]) -> Option<&WideCStr> {
+        match wide.iter().position(|&c| c == 0) {
+            Some(pos) if pos + 1 == wide.len() => {
+                Some(unsafe { WideCStr::from_slice_with_nul_unchecked(wide) })
+            }
+            _ => None,
+        }
+    }
+    /// # Safety
+    /// The slice must end with a nul and contain no interior nuls.
+    pub unsafe fn from_slice_with_nul_unchecked(wide: &[u16]) -> &WideCStr {
+        &*(wide as *const [u16] as *const WideCStr)
+    }
+    /// Borrows a nul terminated wide string returned by Windows API.
+    /// # Safety
+    /// `ptr` must be non-null and point to a valid nul terminated wide string which outlives the
+    /// returned reference.
+    pub unsafe fn from_ptr<'a>(ptr: *const u16) -> &'a WideCStr {
+        assert!(!ptr.is_null());
+        let len = (0..).take_while(|&i| *ptr.add(i) != 0).count();
+        WideCStr::from_slice_with_nul_unchecked(from_raw_parts(ptr, len + 1))
+    }
+    /// Use this to get a pointer to pass to FFI functions.
+    pub fn as_ptr(&self) -> *const u16 {
+        self.0.as_ptr()
+    }
+    /// The length in elements, not including the nul terminator.
+    pub fn len(&self) -> usize {
+        self.0.len() - 1
+    }
+    pub fn as_slice(&self) -> &[u16] {
+        &self.0[..self.len()]
+    }
+    pub fn as_slice_with_nul(&self) -> &[u16] {
+        &self.0
+    }
+    pub fn to_os_string(&self) -> OsString {
+        <OsString as OsStringExt>::from_wide(self.as_slice())
+    }
+}
+impl ToWide for WideCStr {
+    #[inline]
+    fn to_wide(&self) -> Vec<u16> {
+        self.as_slice().to_vec()
+    }
+    #[inline]
+    fn to_wide_null(&self) -> Vec<u16> {
+        self.as_slice_with_nul().to_vec()
+    }
+}
+/// An owned nul terminated wide string, the UTF-16 counterpart to `CString`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct WideCString(Vec<u16>);
+impl WideCString {
+    /// Fails if the string contains an interior nul.
+    pub fn new<T>(s: T) -> Result<WideCString, NulError>
+    where
+        T: ToWide,
+    {
+        s.try_to_wide_null().map(WideCString)
+    }
+    /// Appends a string, failing if it contains an interior nul.
+    /// The position in the error is relative to the appended string.
+    pub fn push_str<T>(&mut self, s: T) -> Result<(), NulError>
+    where
+        T: ToWide,
+    {
+        let wide = s.try_to_wide_null()?;
+        self.0.pop();
+        self.0.extend(wide);
+        Ok(())
+    }
+    pub fn as_wide_c_str(&self) -> &WideCStr {
+        unsafe { WideCStr::from_slice_with_nul_unchecked(&self.0) }
+    }
+    /// Returns the underlying vector including the nul terminator.
+    pub fn into_vec_with_nul(self) -> Vec<u16> {
+        self.0
+    }
+}
+impl Default for WideCString {
+    fn default() -> WideCString {
+        WideCString(vec![0])
+    }
+}
+impl Deref for WideCString {
+    type Target = WideCStr;
+    fn deref(&self) -> &WideCStr {
+        self.as_wide_c_str()
+    }
+}
+impl ToWide for WideCString {
+    #[inline]
+    fn to_wide(&self) -> Vec<u16> {
+        self.as_wide_c_str().to_wide()
+    }
+    #[inline]
+    fn to_wide_null(&self) -> Vec<u16> {
+        self.as_wide_c_str().to_wide_null()
+    }
+}
+/// Stops at the first nul, like `from_wide_null`.
+impl FromWide for WideCString {
+    #[inline]
+    fn from_wide(wide: &[u16]) -> WideCString {
+        let mut vec = wide[..wide_len(wide)].to_vec();
+        vec.push(0);
+        WideCString(vec)
+    }
+}
+/// The length of a wide string up to but not including the first nul, or the whole buffer if
+/// there is none. This is the length `FromWide::from_wide_null` converts.
+pub fn wide_len(buf: &[u16]) -> usize {
+    buf.iter().position(|&c| c == 0).unwrap_or(buf.len())
+}
+/// Strips the run of nuls at the end of a buffer, such as the unused space left in a fixed size
+/// buffer filled by Windows API. Unlike `wide_len` any interior nuls are kept.
+pub fn trim_trailing_nulls(buf: &[u16]) -> &[u16] {
+    let len = buf.iter().rposition(|&c| c != 0).map_or(0, |pos| pos + 1);
+    &buf[..len]
+}
+/// Encodes a single character as UTF-16 without allocating, returning the code units and how
+/// many of them are used, which is 2 for characters outside the Basic Multilingual Plane that
+/// need a surrogate pair.
+pub fn char_to_wide(c: char) -> ([u16; 2], usize) {
+    let mut buf = [0; 2];
+    let len = c.encode_utf16(&mut buf).len();
+    (buf, len)
+}
+/// Encodes a list of strings as a double nul terminated multi-string, as used by `REG_MULTI_SZ`.
+/// Each string is followed by a nul and the list is terminated by an additional nul.
+/// An empty list produces a lone nul. Empty strings cannot be represented since they would
+/// terminate the list early.
+pub fn to_wide_multi<I>(items: I) -> Vec<u16>
+where
+    I: IntoIterator,
+    I::Item: AsRef<OsStr>,
+{
+    let mut wide = Vec::new();
+    for item in items {
+        wide.extend(item.as_ref().encode_wide().chain(Some(0)));
+    }
+    wide.push(0);
+    wide
+}
+/// Decodes a double nul terminated multi-string, stopping at the first empty string.
+/// Buffers which end with only a single nul, or no nul at all, are also accepted.
+pub fn from_wide_multi(buf: &[u16]) -> Vec<OsString> {
+    buf.split(|&c| c == 0)
+        .take_while(|s| !s.is_empty())
+        .map(<OsString as OsStringExt>::from_wide)
+        .collect()
+}
+/// Compares two wide strings for equality ignoring case, using the same ordinal comparison as
+/// the file system and the registry.
+/// # Panics
+/// Panics if `CompareStringOrdinal` fails, which it only does for invalid arguments that these
+/// functions never pass.
+pub fn eq_ignore_case_wide(a: &[u16], b: &[u16]) -> bool {
+    compare_ordinal(a, b, TRUE) == Ordering::Equal
+}
+/// Compares two wide strings ordinally, which is a comparison of the code units.
+/// # Panics
+/// Panics if `CompareStringOrdinal` fails, as for `eq_ignore_case_wide`.
+pub fn cmp_wide(a: &[u16], b: &[u16]) -> Ordering {
+    compare_ordinal(a, b, FALSE)
+}
+/// Compares two wide strings ordinally ignoring case.
+/// # Panics
+/// Panics if `CompareStringOrdinal` fails, as for `eq_ignore_case_wide`.
+pub fn cmp_ignore_case_wide(a: &[u16], b: &[u16]) -> Ordering {
+    compare_ordinal(a, b, TRUE)
+}
+fn compare_ordinal(a: &[u16], b: &[u16], ignore_case: BOOL) -> Ordering {
+    // The lengths are passed as an i32, so longer strings are compared a chunk at a time. Ordinal
+    // comparisons map each code unit on its own, so comparing equal prefixes first gives the same
+    // result as comparing the whole strings.
+    const CHUNK: usize = i32::MAX as usize;
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (a_chunk, a_rest) = a.split_at(a.len().min(CHUNK));
+        let (b_chunk, b_rest) = b.split_at(b.len().min(CHUNK));
+        let res = unsafe {
+            CompareStringOrdinal(
+                a_chunk.as_ptr(),
+                a_chunk.len() as i32,
+                b_chunk.as_ptr(),
+                b_chunk.len() as i32,
+                ignore_case,
+            )
+        };
+        match res {
+            CSTR_LESS_THAN => return Ordering::Less,
+            CSTR_GREATER_THAN => return Ordering::Greater,
+            CSTR_EQUAL if a_rest.is_empty() && b_rest.is_empty() => return Ordering::Equal,
+            // Equal chunks are both full, so neither string has ended yet
+            CSTR_EQUAL => {
+                a = a_rest;
+                b = b_rest;
+            }
+            _ => panic!("CompareStringOrdinal failed: {}", WinError::last()),
+        }
+    }
+}